@@ -0,0 +1,95 @@
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use jtd::Schema;
+use rand::SeedableRng;
+use serde_json::json;
+
+fn schemas() -> Vec<(&'static str, Schema)> {
+    let schemas = vec![
+        ("empty", json!({})),
+        (
+            "user",
+            json!({
+                "properties": {
+                    "id": { "type": "string" },
+                    "name": {
+                        "type": "string",
+                        "metadata": { "fuzzHint": "en_us/names/full_name" }
+                    },
+                    "email": {
+                        "type": "string",
+                        "metadata": { "fuzzHint": "en_us/internet/email" }
+                    },
+                    "createdAt": { "type": "timestamp" },
+                    "role": { "enum": ["admin", "member", "guest"] },
+                    "scores": { "elements": { "type": "float64" }},
+                    "labels": { "values": { "type": "string" }}
+                },
+                "optionalProperties": {
+                    "age": { "type": "uint8" },
+                    "address": {
+                        "type": "string",
+                        "nullable": true,
+                        "metadata": { "fuzzHint": "en_us/addresses/address" }
+                    }
+                },
+                "additionalProperties": true
+            }),
+        ),
+        (
+            "recursive",
+            json!({
+                "definitions": {
+                    "node": {
+                        "properties": {
+                            "value": { "type": "int32" },
+                            "children": { "elements": { "ref": "node" }}
+                        }
+                    },
+                    "shape": {
+                        "discriminator": "kind",
+                        "mapping": {
+                            "leaf": { "properties": { "n": { "type": "float32" }}},
+                            "branch": {
+                                "properties": { "shapes": { "values": { "ref": "shape" }}}
+                            }
+                        }
+                    }
+                },
+                "properties": {
+                    "node": { "ref": "node" },
+                    "shape": { "ref": "shape" }
+                }
+            }),
+        ),
+    ];
+
+    schemas
+        .into_iter()
+        .map(|(name, schema)| {
+            let schema = serde_json::from_value(schema).unwrap();
+            (name, Schema::from_serde_schema(schema).unwrap())
+        })
+        .collect()
+}
+
+fn bench_fuzz(c: &mut Criterion) {
+    for (name, schema) in schemas() {
+        let mut group = c.benchmark_group(name);
+
+        group.bench_function("fuzz", |b| {
+            let mut rng = rand_pcg::Pcg32::seed_from_u64(8927);
+            b.iter(|| jtd_fuzz::fuzz(black_box(&schema), &mut rng))
+        });
+
+        group.bench_function("CompiledFuzzer", |b| {
+            let fuzzer = jtd_fuzz::CompiledFuzzer::new(&schema);
+            let mut rng = rand_pcg::Pcg32::seed_from_u64(8927);
+            b.iter(|| black_box(&fuzzer).fuzz(&mut rng))
+        });
+
+        group.finish();
+    }
+}
+
+criterion_group!(benches, bench_fuzz);
+criterion_main!(benches);
@@ -0,0 +1,211 @@
+use jtd::Schema;
+use std::collections::BTreeMap;
+use thiserror::Error;
+
+/// Indicates that some of a schema's definitions have no finite instance.
+///
+/// Such a schema is perfectly valid as far as JSON Typedef is concerned, but no
+/// JSON value can ever satisfy it, and so there is nothing to generate. For
+/// instance, this schema describes a linked list with no end:
+///
+/// ```json
+/// {
+///   "definitions": {
+///     "node": { "properties": { "next": { "ref": "node" }}}
+///   },
+///   "ref": "node"
+/// }
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq, Error)]
+#[error("definitions have no finite instance: {definitions:?}")]
+pub struct UnsatisfiableError {
+    /// The names of the definitions with no finite instance, in sorted order.
+    pub definitions: Vec<String>,
+}
+
+/// Computes the minimum depth of an instance of each of a schema's
+/// definitions.
+///
+/// The depth of `null`, booleans, numbers, and strings is zero. The depth of an
+/// array or object is one more than the depth of its deepest element or member.
+/// So `[]` and `{}` have a depth of one, and `[{}]` has a depth of two.
+///
+/// If any definition has no finite instance, then [`fuzz`](crate::fuzz) would
+/// never finish generating data for it, and this function returns an
+/// [`UnsatisfiableError`] listing those definitions instead.
+///
+/// ```
+/// use serde_json::json;
+///
+/// let schema = jtd::Schema::from_serde_schema(serde_json::from_value(json!({
+///     "definitions": {
+///         "list": {
+///             "properties": { "next": { "ref": "list", "nullable": true }}
+///         },
+///         "loop": {
+///             "properties": { "next": { "ref": "loop" }}
+///         }
+///     }
+/// })).unwrap()).unwrap();
+///
+/// assert_eq!(
+///     jtd_fuzz::min_depths(&schema),
+///     Err(jtd_fuzz::UnsatisfiableError { definitions: vec!["loop".into()] }),
+/// );
+/// ```
+pub fn min_depths(schema: &Schema) -> Result<BTreeMap<String, usize>, UnsatisfiableError> {
+    let depths = compute_min_depths(schema);
+
+    let definitions: Vec<_> = depths
+        .iter()
+        .filter(|(_, depth)| depth.is_none())
+        .map(|(name, _)| name.clone())
+        .collect();
+
+    if !definitions.is_empty() {
+        return Err(UnsatisfiableError { definitions });
+    }
+
+    Ok(depths
+        .into_iter()
+        .map(|(name, depth)| (name, depth.unwrap()))
+        .collect())
+}
+
+/// The minimum depth of an instance of each of a root schema's definitions.
+///
+/// `None` means that a definition has no finite instance at all.
+pub(crate) type MinDepths = BTreeMap<String, Option<usize>>;
+
+/// Computes the minimum depth of an instance of each of `root`'s definitions,
+/// as described in [`min_depths`].
+pub(crate) fn compute_min_depths(root: &Schema) -> MinDepths {
+    // Start by assuming no definition has a finite instance, and then keep
+    // lowering each definition's depth until nothing changes. Each pass either
+    // changes nothing, or finds a finite depth for at least one more
+    // definition, so this takes at most one pass per definition, plus one.
+    let mut depths: MinDepths = root
+        .definitions()
+        .keys()
+        .map(|k| (k.clone(), None))
+        .collect();
+
+    loop {
+        let mut changed = false;
+
+        for (name, definition) in root.definitions() {
+            let depth = min_depth(&depths, definition);
+            if depth != depths[name] {
+                depths.insert(name.clone(), depth);
+                changed = true;
+            }
+        }
+
+        if !changed {
+            return depths;
+        }
+    }
+}
+
+/// Computes the minimum depth of an instance of `schema`, given the minimum
+/// depths of the root schema's definitions.
+pub(crate) fn min_depth(depths: &MinDepths, schema: &Schema) -> Option<usize> {
+    if schema.nullable() {
+        return Some(0);
+    }
+
+    match schema {
+        Schema::Empty { .. } | Schema::Type { .. } | Schema::Enum { .. } => Some(0),
+
+        // A ref to a definition that doesn't exist has no instances.
+        Schema::Ref { ref ref_, .. } => depths.get(ref_).copied().flatten(),
+
+        Schema::Elements { .. } | Schema::Values { .. } => Some(1),
+
+        Schema::Properties { ref properties, .. } => properties
+            .values()
+            .map(|s| min_depth(depths, s))
+            .try_fold(0, |max, depth| depth.map(|d| max.max(d)))
+            .map(|d| d + 1),
+
+        Schema::Discriminator { ref mapping, .. } => {
+            mapping.values().filter_map(|s| min_depth(depths, s)).min()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_min_depths() {
+        assert_min_depths(
+            json!({
+                "definitions": {
+                    "a": { "type": "string" },
+                    "b": { "elements": { "ref": "b" }},
+                    "c": { "properties": { "x": { "ref": "d" }}},
+                    "d": { "properties": { "y": { "ref": "a" }}},
+                    "e": { "ref": "c" },
+                    "f": {
+                        "discriminator": "type",
+                        "mapping": {
+                            "x": { "properties": { "x": { "ref": "f" }}},
+                            "y": { "properties": { "y": { "ref": "c" }}},
+                        }
+                    },
+                    "g": { "properties": { "g": { "ref": "g", "nullable": true }}},
+                },
+            }),
+            Ok(vec![
+                ("a", 0),
+                ("b", 1),
+                ("c", 2),
+                ("d", 1),
+                ("e", 2),
+                ("f", 3),
+                ("g", 1),
+            ]),
+        );
+    }
+
+    #[test]
+    fn test_min_depths_unsatisfiable() {
+        assert_min_depths(
+            json!({
+                "definitions": {
+                    "a": { "ref": "a" },
+                    "b": { "properties": { "b": { "ref": "b" }}},
+                    "c": { "elements": { "ref": "b" }},
+                    "d": { "properties": { "d": { "ref": "b" }}},
+                    "e": {
+                        "discriminator": "type",
+                        "mapping": {
+                            "x": { "properties": { "x": { "ref": "e" }}},
+                            "y": { "properties": { "y": { "ref": "b" }}},
+                        }
+                    },
+                },
+            }),
+            Err(vec!["a", "b", "d", "e"]),
+        );
+    }
+
+    fn assert_min_depths(
+        schema: serde_json::Value,
+        expected: Result<Vec<(&str, usize)>, Vec<&str>>,
+    ) {
+        let schema = Schema::from_serde_schema(serde_json::from_value(schema).unwrap()).unwrap();
+        schema.validate().unwrap();
+
+        let expected = expected
+            .map(|depths| depths.into_iter().map(|(k, v)| (k.to_owned(), v)).collect())
+            .map_err(|definitions| UnsatisfiableError {
+                definitions: definitions.into_iter().map(String::from).collect(),
+            });
+
+        assert_eq!(expected, min_depths(&schema));
+    }
+}
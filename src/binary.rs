@@ -0,0 +1,366 @@
+use crate::stream::{serialize_with_style, Style};
+use crate::FuzzConfig;
+#[cfg(feature = "cbor")]
+use chrono::DateTime;
+#[cfg(feature = "cbor")]
+use ciborium::tag::Captured;
+use jtd::Schema;
+#[cfg(feature = "cbor")]
+use serde::ser::{Serialize, Serializer};
+#[cfg(feature = "cbor")]
+use std::cell::RefCell;
+use std::io;
+
+/// How [`fuzz_to_cbor_with_config`] writes `timestamp` values.
+#[cfg(feature = "cbor")]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CborTimestamps {
+    /// Write timestamps as plain RFC3339 text strings, just like in JSON.
+    ///
+    /// This is what [`fuzz_to_cbor`] does.
+    Text,
+
+    /// Write timestamps as RFC3339 text strings tagged with tag 0, the standard
+    /// date/time string tag.
+    DateTime,
+
+    /// Write timestamps as the number of seconds since the Unix epoch, tagged
+    /// with tag 1, the epoch-based date/time tag. Timestamps with fractional
+    /// seconds are written as floats, and so may lose some precision; the rest
+    /// are written as integers. The timestamp's offset from UTC isn't kept.
+    Epoch,
+}
+
+/// Generates a single random value satisfying a given schema, and writes it to
+/// `writer` as [CBOR](https://cbor.io).
+///
+/// This writes exactly what serializing the output of [`fuzz`](crate::fuzz) as
+/// CBOR would, given an RNG in the same state, and leaves the RNG in the same
+/// state afterwards. Like [`fuzz_to_writer`](crate::fuzz_to_writer), the value
+/// is written out as it's generated.
+///
+/// ```
+/// use serde_json::json;
+/// use rand::SeedableRng;
+///
+/// let schema = jtd::Schema::from_serde_schema(serde_json::from_value(json!({
+///     "elements": { "type": "uint8" }
+/// })).unwrap()).unwrap();
+///
+/// let mut rng = rand_pcg::Pcg32::seed_from_u64(8927);
+/// let mut out = Vec::new();
+/// jtd_fuzz::fuzz_to_cbor(&schema, &mut rng, &mut out).unwrap();
+///
+/// let mut rng = rand_pcg::Pcg32::seed_from_u64(8927);
+/// let mut expected = Vec::new();
+/// ciborium::into_writer(&jtd_fuzz::fuzz(&schema, &mut rng), &mut expected).unwrap();
+/// assert_eq!(out, expected);
+/// ```
+///
+/// This requires the `cbor` feature.
+#[cfg(feature = "cbor")]
+pub fn fuzz_to_cbor<R: rand::Rng + Clone, W: io::Write>(
+    schema: &Schema,
+    rng: &mut R,
+    writer: W,
+) -> Result<(), ciborium::ser::Error<io::Error>> {
+    fuzz_to_cbor_with_config(
+        schema,
+        &FuzzConfig::default(),
+        CborTimestamps::Text,
+        rng,
+        writer,
+    )
+}
+
+/// Generates a single random value satisfying a given schema, using the given
+/// configuration, and writes it to `writer` as CBOR, with timestamps written as
+/// `timestamps` says.
+///
+/// See [`fuzz_to_cbor`] for details.
+#[cfg(feature = "cbor")]
+pub fn fuzz_to_cbor_with_config<R: rand::Rng + Clone, W: io::Write>(
+    schema: &Schema,
+    config: &FuzzConfig,
+    timestamps: CborTimestamps,
+    rng: &mut R,
+    writer: W,
+) -> Result<(), ciborium::ser::Error<io::Error>> {
+    let value = Streamed {
+        schema,
+        config,
+        style: Style {
+            timestamps,
+            ..Style::JSON
+        },
+        rng: RefCell::new(rng),
+    };
+
+    ciborium::into_writer(&value, writer)
+}
+
+// A value that's generated as it's serialized. ciborium's serializer can only
+// be reached through into_writer, and so needs something to serialize.
+#[cfg(feature = "cbor")]
+struct Streamed<'a, R> {
+    schema: &'a Schema,
+    config: &'a FuzzConfig,
+    style: Style,
+    rng: RefCell<&'a mut R>,
+}
+
+#[cfg(feature = "cbor")]
+impl<'a, R: rand::Rng + Clone> Serialize for Streamed<'a, R> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut rng = self.rng.borrow_mut();
+        serialize_with_style(self.schema, self.config, self.style, *rng, serializer)
+    }
+}
+
+/// Generates a single random value satisfying a given schema, and writes it to
+/// `writer` as [MessagePack](https://msgpack.org).
+///
+/// This writes exactly what serializing the output of [`fuzz`](crate::fuzz) as
+/// MessagePack would, given an RNG in the same state, and leaves the RNG in the
+/// same state afterwards. Like [`fuzz_to_writer`](crate::fuzz_to_writer), the
+/// value is written out as it's generated.
+///
+/// ```
+/// use serde_json::json;
+/// use rand::SeedableRng;
+///
+/// let schema = jtd::Schema::from_serde_schema(serde_json::from_value(json!({
+///     "elements": { "type": "uint8" }
+/// })).unwrap()).unwrap();
+///
+/// let mut rng = rand_pcg::Pcg32::seed_from_u64(8927);
+/// let mut out = Vec::new();
+/// jtd_fuzz::fuzz_to_msgpack(&schema, &mut rng, &mut out).unwrap();
+///
+/// let mut rng = rand_pcg::Pcg32::seed_from_u64(8927);
+/// assert_eq!(out, rmp_serde::to_vec(&jtd_fuzz::fuzz(&schema, &mut rng)).unwrap());
+/// ```
+///
+/// This requires the `msgpack` feature.
+#[cfg(feature = "msgpack")]
+pub fn fuzz_to_msgpack<R: rand::Rng + Clone, W: io::Write>(
+    schema: &Schema,
+    rng: &mut R,
+    writer: W,
+) -> Result<(), rmp_serde::encode::Error> {
+    fuzz_to_msgpack_with_config(schema, &FuzzConfig::default(), rng, writer)
+}
+
+/// Generates a single random value satisfying a given schema, using the given
+/// configuration, and writes it to `writer` as MessagePack.
+///
+/// See [`fuzz_to_msgpack`] for details.
+#[cfg(feature = "msgpack")]
+pub fn fuzz_to_msgpack_with_config<R: rand::Rng + Clone, W: io::Write>(
+    schema: &Schema,
+    config: &FuzzConfig,
+    rng: &mut R,
+    mut writer: W,
+) -> Result<(), rmp_serde::encode::Error> {
+    let mut serializer = rmp_serde::Serializer::new(&mut writer);
+    serialize_with_style(schema, config, Style::JSON, rng, &mut serializer)
+}
+
+// Writes a generated timestamp.
+#[cfg(feature = "cbor")]
+pub(crate) fn serialize_timestamp<S: Serializer>(
+    timestamp: &str,
+    timestamps: CborTimestamps,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    match timestamps {
+        CborTimestamps::Text => serializer.serialize_str(timestamp),
+        CborTimestamps::DateTime => Captured(Some(0), timestamp).serialize(serializer),
+        CborTimestamps::Epoch => {
+            // Every generated timestamp parses, but fall back to a date/time
+            // string just in case.
+            let instant = match DateTime::parse_from_rfc3339(timestamp) {
+                Ok(instant) => instant,
+                Err(_) => return Captured(Some(0), timestamp).serialize(serializer),
+            };
+
+            // Leap seconds have more than a billion nanoseconds.
+            let secs = instant.timestamp();
+            let nanos = instant.timestamp_subsec_nanos();
+            if nanos == 0 {
+                Captured(Some(1), secs).serialize(serializer)
+            } else {
+                let epoch = secs as f64 + nanos as f64 / 1e9;
+                Captured(Some(1), epoch).serialize(serializer)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{assert_matches_fuzz, recursive_schema};
+
+    #[cfg(feature = "cbor")]
+    #[test]
+    fn test_fuzz_to_cbor() {
+        let schema = recursive_schema();
+
+        let to_cbor = |instance| {
+            let mut cbor = Vec::new();
+            ciborium::into_writer(&instance, &mut cbor).unwrap();
+            cbor
+        };
+
+        assert_matches_fuzz(&schema, &FuzzConfig::new(), 100, to_cbor, |rng| {
+            let mut cbor = Vec::new();
+            fuzz_to_cbor(&schema, rng, &mut cbor).unwrap();
+            cbor
+        });
+    }
+
+    #[cfg(feature = "msgpack")]
+    #[test]
+    fn test_fuzz_to_msgpack() {
+        let schema = recursive_schema();
+
+        // Numbers at the edges of their types, and keys beyond ASCII, must
+        // use the same MessagePack types as they do coming from a Value.
+        let config = FuzzConfig::new()
+            .with_number_strategy(crate::NumberStrategy::Edges)
+            .with_char_classes(vec![crate::CharClass::Astral]);
+
+        for config in &[FuzzConfig::new(), config] {
+            let to_msgpack = |instance| rmp_serde::to_vec(&instance).unwrap();
+            assert_matches_fuzz(&schema, config, 100, to_msgpack, |rng| {
+                let mut msgpack = Vec::new();
+                fuzz_to_msgpack_with_config(&schema, config, rng, &mut msgpack).unwrap();
+                msgpack
+            });
+        }
+    }
+
+    #[cfg(feature = "cbor")]
+    #[test]
+    fn test_cbor_tag_roundtrip() {
+        use ciborium::Value as CborValue;
+
+        // Removes the date/time tags from timestamps.
+        fn untag(value: CborValue) -> CborValue {
+            match value {
+                CborValue::Tag(0, value) => *value,
+                CborValue::Array(values) => {
+                    CborValue::Array(values.into_iter().map(untag).collect())
+                }
+                CborValue::Map(members) => CborValue::Map(
+                    members
+                        .into_iter()
+                        .map(|(k, v)| (untag(k), untag(v)))
+                        .collect(),
+                ),
+                value => value,
+            }
+        }
+
+        // Timestamps nested anywhere in a value are tagged, and apart from
+        // those tags, the value is just what fuzz generates.
+        let schema = recursive_schema();
+        let config = FuzzConfig::new();
+        let mut tags = 0;
+        assert_matches_fuzz(
+            &schema,
+            &config,
+            100,
+            |instance| CborValue::serialized(&instance).unwrap(),
+            |rng| {
+                let mut cbor = Vec::new();
+                fuzz_to_cbor_with_config(
+                    &schema,
+                    &config,
+                    CborTimestamps::DateTime,
+                    rng,
+                    &mut cbor,
+                )
+                .unwrap();
+
+                let tagged: CborValue = ciborium::from_reader(cbor.as_slice()).unwrap();
+                let untagged = untag(tagged.clone());
+                if tagged != untagged {
+                    tags += 1;
+                }
+
+                untagged
+            },
+        );
+
+        assert!(tags > 0);
+    }
+
+    #[cfg(feature = "cbor")]
+    #[test]
+    fn test_cbor_timestamps() {
+        use crate::testing::parse_schema;
+        use ciborium::Value as CborValue;
+        use rand::SeedableRng;
+        use serde_json::{json, Value};
+
+        let schema = parse_schema(json!({ "type": "timestamp" }));
+
+        let configs = [
+            FuzzConfig::new(),
+            FuzzConfig::new().with_timestamp_precision(3),
+            FuzzConfig::new().with_timestamp_edges(true),
+        ];
+
+        let mut rng = rand_pcg::Pcg32::seed_from_u64(8927);
+        for config in &configs {
+            for _ in 0..100 {
+                let instance = crate::fuzz_with_config(&schema, config, &mut rng.clone());
+                let timestamp = instance.as_str().unwrap();
+                let instant = DateTime::parse_from_rfc3339(timestamp).unwrap();
+
+                let text = fuzz_to_cbor_value(&schema, config, CborTimestamps::Text, &rng);
+                assert_eq!(CborValue::Text(timestamp.to_owned()), text);
+
+                let date_time = fuzz_to_cbor_value(&schema, config, CborTimestamps::DateTime, &rng);
+                assert_eq!(
+                    CborValue::Tag(0, Box::new(CborValue::Text(timestamp.to_owned()))),
+                    date_time
+                );
+
+                let epoch = match fuzz_to_cbor_value(&schema, config, CborTimestamps::Epoch, &rng) {
+                    CborValue::Tag(1, epoch) => match *epoch {
+                        CborValue::Integer(secs) => i128::from(secs) as f64,
+                        CborValue::Float(secs) => secs,
+                        epoch => panic!("epoch is not a number: {:?}", epoch),
+                    },
+                    epoch => panic!("epoch is not tagged: {:?}", epoch),
+                };
+                let expected =
+                    instant.timestamp() as f64 + instant.timestamp_subsec_nanos() as f64 / 1e9;
+                assert_eq!(expected, epoch);
+
+                crate::fuzz_with_config(&schema, config, &mut rng);
+            }
+        }
+
+        // Timestamps elsewhere, like in JSON, are never tagged.
+        let instance: Value = crate::fuzz(&schema, &mut rng.clone());
+        let mut json = Vec::new();
+        crate::fuzz_to_writer(&schema, &mut rng, &mut json).unwrap();
+        assert_eq!(instance, serde_json::from_slice::<Value>(&json).unwrap());
+    }
+
+    #[cfg(feature = "cbor")]
+    fn fuzz_to_cbor_value<R: rand::Rng + Clone>(
+        schema: &Schema,
+        config: &FuzzConfig,
+        timestamps: CborTimestamps,
+        rng: &R,
+    ) -> ciborium::Value {
+        let mut out = Vec::new();
+        fuzz_to_cbor_with_config(schema, config, timestamps, &mut rng.clone(), &mut out).unwrap();
+        ciborium::from_reader(out.as_slice()).unwrap()
+    }
+}
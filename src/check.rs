@@ -0,0 +1,354 @@
+use crate::analysis::{self, MinDepths};
+use crate::{fuzz_with_config, FuzzConfig};
+use jtd::Schema;
+use serde_json::Value;
+use std::collections::BTreeSet;
+use thiserror::Error;
+
+/// Indicates that a schema has no instances that can be generated.
+///
+/// [`fuzz`](crate::fuzz) assumes that its schema is valid, and panics (or, for
+/// unsatisfiable schemas, never returns) if it isn't. [`try_fuzz`] checks for
+/// these problems ahead of time instead, and returns one of these errors.
+///
+/// Each variant carries a path to the schema with the problem, made of the
+/// keywords and names leading to it from the root schema. For instance, the
+/// path to the `ref` in `{"properties": {"a": {"ref": "b"}}}` is `["properties",
+/// "a"]`.
+#[derive(Clone, Debug, PartialEq, Eq, Error)]
+pub enum FuzzError {
+    /// A `ref` names a definition that doesn't exist.
+    #[error("ref to missing definition {definition:?} at {schema_path:?}")]
+    MissingDefinition {
+        /// A path to the schema with the `ref`.
+        schema_path: Vec<String>,
+
+        /// The name of the missing definition.
+        definition: String,
+    },
+
+    /// An `enum` has no values to choose from.
+    #[error("enum with no values at {schema_path:?}")]
+    EmptyEnum {
+        /// A path to the schema with the `enum`.
+        schema_path: Vec<String>,
+    },
+
+    /// A `discriminator` has no variants to choose from.
+    #[error("discriminator with empty mapping at {schema_path:?}")]
+    EmptyMapping {
+        /// A path to the schema with the `discriminator`.
+        schema_path: Vec<String>,
+    },
+
+    /// A `discriminator` variant isn't a non-nullable `properties` schema, and
+    /// so there'd be no object to add the discriminator's tag to.
+    #[error("discriminator variant is not an object schema at {schema_path:?}")]
+    NonObjectVariant {
+        /// A path to the variant.
+        schema_path: Vec<String>,
+    },
+
+    /// A `ref` names a definition that has no finite instance. See
+    /// [`min_depths`](crate::min_depths) for details.
+    #[error("ref to unsatisfiable definition {definition:?} at {schema_path:?}")]
+    Unsatisfiable {
+        /// A path to the schema with the `ref`.
+        schema_path: Vec<String>,
+
+        /// The name of the definition with no finite instance.
+        definition: String,
+    },
+}
+
+/// Generates a single random JSON value satisfying a given schema, or returns
+/// an error if there's nothing that could be generated.
+///
+/// This is like [`fuzz`](crate::fuzz), except that it first checks the parts of
+/// the schema that could be generated for problems that would make `fuzz` panic
+/// or never return. If there are none, it returns exactly what `fuzz` would.
+/// Parts of the schema that can't be reached from the root schema, such as
+/// unused definitions, aren't checked.
+///
+/// Checking takes time proportional to the size of the schema, and is done on
+/// every call.
+///
+/// ```
+/// use serde_json::json;
+/// use rand::SeedableRng;
+///
+/// let schema = jtd::Schema::from_serde_schema(serde_json::from_value(json!({
+///     "properties": {
+///         "user": { "ref": "user" }
+///     }
+/// })).unwrap()).unwrap();
+///
+/// let mut rng = rand_pcg::Pcg32::seed_from_u64(8927);
+/// assert_eq!(
+///     jtd_fuzz::try_fuzz(&schema, &mut rng),
+///     Err(jtd_fuzz::FuzzError::MissingDefinition {
+///         schema_path: vec!["properties".into(), "user".into()],
+///         definition: "user".into(),
+///     }),
+/// );
+/// ```
+pub fn try_fuzz<R: rand::Rng>(schema: &Schema, rng: &mut R) -> Result<Value, FuzzError> {
+    try_fuzz_with_config(schema, &FuzzConfig::default(), rng)
+}
+
+/// Generates a single random JSON value satisfying a given schema, using the
+/// given configuration, or returns an error if there's nothing that could be
+/// generated.
+///
+/// See [`try_fuzz`] and [`fuzz_with_config`] for details.
+pub fn try_fuzz_with_config<R: rand::Rng>(
+    schema: &Schema,
+    config: &FuzzConfig,
+    rng: &mut R,
+) -> Result<Value, FuzzError> {
+    Checker {
+        root: schema,
+        min_depths: analysis::compute_min_depths(schema),
+        checked_definitions: BTreeSet::new(),
+        schema_path: Vec::new(),
+    }
+    .check(schema)?;
+
+    Ok(fuzz_with_config(schema, config, rng))
+}
+
+struct Checker<'a> {
+    root: &'a Schema,
+    min_depths: MinDepths,
+
+    // Definitions that have already been checked, or are being checked, so
+    // that each is checked once, and recursive definitions terminate.
+    checked_definitions: BTreeSet<&'a str>,
+
+    schema_path: Vec<String>,
+}
+
+impl<'a> Checker<'a> {
+    fn check(&mut self, schema: &'a Schema) -> Result<(), FuzzError> {
+        match schema {
+            Schema::Empty { .. } | Schema::Type { .. } => Ok(()),
+
+            Schema::Ref { ref ref_, .. } => {
+                let root = self.root;
+                let definition = match root.definitions().get(ref_) {
+                    Some(definition) => definition,
+                    None => {
+                        return Err(FuzzError::MissingDefinition {
+                            schema_path: self.schema_path.clone(),
+                            definition: ref_.clone(),
+                        })
+                    }
+                };
+
+                if self.min_depths[ref_].is_none() {
+                    return Err(FuzzError::Unsatisfiable {
+                        schema_path: self.schema_path.clone(),
+                        definition: ref_.clone(),
+                    });
+                }
+
+                if !self.checked_definitions.insert(ref_) {
+                    return Ok(());
+                }
+
+                // Report problems inside of the definition by where they are
+                // in the definition, rather than by where they're used from.
+                let schema_path = std::mem::replace(
+                    &mut self.schema_path,
+                    vec!["definitions".to_owned(), ref_.clone()],
+                );
+                let result = self.check(definition);
+                self.schema_path = schema_path;
+
+                result
+            }
+
+            Schema::Enum { ref enum_, .. } => {
+                if enum_.is_empty() {
+                    return Err(FuzzError::EmptyEnum {
+                        schema_path: self.schema_path.clone(),
+                    });
+                }
+
+                Ok(())
+            }
+
+            Schema::Elements { ref elements, .. } => self.check_at(&["elements"], elements),
+
+            Schema::Properties {
+                ref properties,
+                ref optional_properties,
+                ..
+            } => {
+                for (k, s) in properties {
+                    self.check_at(&["properties", k], s)?;
+                }
+
+                for (k, s) in optional_properties {
+                    self.check_at(&["optionalProperties", k], s)?;
+                }
+
+                Ok(())
+            }
+
+            Schema::Values { ref values, .. } => self.check_at(&["values"], values),
+
+            Schema::Discriminator { ref mapping, .. } => {
+                if mapping.is_empty() {
+                    return Err(FuzzError::EmptyMapping {
+                        schema_path: self.schema_path.clone(),
+                    });
+                }
+
+                for (k, s) in mapping {
+                    match s {
+                        Schema::Properties {
+                            nullable: false, ..
+                        } => {}
+                        _ => {
+                            let mut schema_path = self.schema_path.clone();
+                            schema_path.extend(vec!["mapping".to_owned(), k.clone()]);
+
+                            return Err(FuzzError::NonObjectVariant { schema_path });
+                        }
+                    }
+
+                    self.check_at(&["mapping", k], s)?;
+                }
+
+                Ok(())
+            }
+        }
+    }
+
+    fn check_at(&mut self, tokens: &[&str], schema: &'a Schema) -> Result<(), FuzzError> {
+        let len = self.schema_path.len();
+        self.schema_path
+            .extend(tokens.iter().map(|token| token.to_string()));
+        let result = self.check(schema);
+        self.schema_path.truncate(len);
+
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::SeedableRng;
+    use serde_json::json;
+
+    #[test]
+    fn test_try_fuzz() {
+        let schema = json!({
+            "definitions": {
+                "node": {
+                    "properties": {
+                        "children": { "elements": { "ref": "node" }},
+                    },
+                },
+                "loop": { "properties": { "loop": { "ref": "loop" }}},
+            },
+            "properties": {
+                "node": { "ref": "node" },
+                "any": {},
+            },
+        });
+        let schema = Schema::from_serde_schema(serde_json::from_value(schema).unwrap()).unwrap();
+
+        // Unused definitions, like "loop" here, are not a problem.
+        let mut rng = rand_pcg::Pcg32::seed_from_u64(8927);
+        let mut try_rng = rng.clone();
+        for _ in 0..100 {
+            assert_eq!(
+                Ok(crate::fuzz(&schema, &mut rng)),
+                try_fuzz(&schema, &mut try_rng)
+            );
+        }
+    }
+
+    #[test]
+    fn test_try_fuzz_errors() {
+        assert_fuzz_error(
+            json!({
+                "definitions": {
+                    "a": { "elements": { "ref": "b" }},
+                },
+                "properties": { "a": { "ref": "a" }},
+            }),
+            FuzzError::MissingDefinition {
+                schema_path: vec!["definitions".into(), "a".into(), "elements".into()],
+                definition: "b".into(),
+            },
+        );
+
+        assert_fuzz_error(
+            json!({ "values": { "enum": [] }}),
+            FuzzError::EmptyEnum {
+                schema_path: vec!["values".into()],
+            },
+        );
+
+        assert_fuzz_error(
+            json!({
+                "optionalProperties": {
+                    "a": { "discriminator": "type", "mapping": {}},
+                },
+            }),
+            FuzzError::EmptyMapping {
+                schema_path: vec!["optionalProperties".into(), "a".into()],
+            },
+        );
+
+        assert_fuzz_error(
+            json!({
+                "discriminator": "type",
+                "mapping": {
+                    "a": { "properties": {}},
+                    "b": { "properties": {}, "nullable": true },
+                },
+            }),
+            FuzzError::NonObjectVariant {
+                schema_path: vec!["mapping".into(), "b".into()],
+            },
+        );
+
+        assert_fuzz_error(
+            json!({
+                "discriminator": "type",
+                "mapping": {
+                    "a": { "properties": { "b": { "type": "string" }}},
+                    "b": { "elements": {}},
+                },
+            }),
+            FuzzError::NonObjectVariant {
+                schema_path: vec!["mapping".into(), "b".into()],
+            },
+        );
+
+        assert_fuzz_error(
+            json!({
+                "definitions": {
+                    "loop": { "properties": { "loop": { "ref": "loop" }}},
+                },
+                "elements": { "ref": "loop" },
+            }),
+            FuzzError::Unsatisfiable {
+                schema_path: vec!["elements".into()],
+                definition: "loop".into(),
+            },
+        );
+    }
+
+    fn assert_fuzz_error(schema: Value, expected: FuzzError) {
+        let schema = Schema::from_serde_schema(serde_json::from_value(schema).unwrap()).unwrap();
+
+        let mut rng = rand_pcg::Pcg32::seed_from_u64(8927);
+        assert_eq!(Err(expected), try_fuzz(&schema, &mut rng));
+    }
+}
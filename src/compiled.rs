@@ -0,0 +1,612 @@
+use crate::analysis::{self, MinDepths};
+use crate::config::{gen_length, gen_probability};
+use crate::hints::SharedHint;
+use crate::strings::DefinedProperties;
+use crate::{
+    fuzz_empty_primitive, fuzz_type_with_hint, string_generator, string_metadata, strings,
+    FuzzConfig, METADATA_KEY_FUZZ_HINT, METADATA_KEY_FUZZ_PATTERN,
+};
+use jtd::{Schema, Type};
+use rand::seq::{IteratorRandom, SliceRandom};
+use serde_json::Value;
+use std::collections::BTreeMap;
+
+/// A schema that's been prepared ahead of time for generating many values.
+///
+/// [`fuzz`](crate::fuzz) works directly off of a [`Schema`], and so it has to
+/// redo some work for every value it generates: looking up definitions by
+/// name, sorting property names, working out which properties additional
+/// properties must not clash with, and looking up `fuzzHint`s. A
+/// `CompiledFuzzer` does all of that once, up front, and then generates values
+/// more quickly.
+///
+/// Given an RNG in the same state, [`CompiledFuzzer::fuzz`] generates exactly
+/// the same value as [`fuzz_with_config`](crate::fuzz_with_config) does with the
+/// same schema and configuration.
+///
+/// ```
+/// use serde_json::json;
+/// use rand::SeedableRng;
+///
+/// let schema = jtd::Schema::from_serde_schema(serde_json::from_value(json!({
+///     "properties": {
+///         "name": { "type": "string" },
+///         "createdAt": { "type": "timestamp" },
+///         "favoriteNumbers": {
+///             "elements": { "type": "uint8" }
+///         }
+///     }
+/// })).unwrap()).unwrap();
+///
+/// let fuzzer = jtd_fuzz::CompiledFuzzer::new(&schema);
+///
+/// let mut rng = rand_pcg::Pcg32::seed_from_u64(8927);
+/// assert_eq!(fuzzer.fuzz(&mut rng), json!({
+///     "name": "f",
+///     "createdAt": "1931-10-18T16:37:09-03:03",
+///     "favoriteNumbers": [166, 142]
+/// }));
+/// ```
+#[derive(Clone, Debug)]
+pub struct CompiledFuzzer {
+    config: FuzzConfig,
+
+    // Every schema, with the root schema first. Schemas refer to one another by
+    // their index in this list.
+    nodes: Vec<Node>,
+
+    // The index, in nodes, of each of the root's definitions. Definitions are
+    // numbered in sorted order of their names.
+    definitions: Vec<usize>,
+
+    // Schemas that are generated with a context of their own: an empty schema
+    // (for additional properties), and an array and object of empty schemas
+    // (for the recursive cases of an empty root schema).
+    empty: usize,
+    empty_elements: usize,
+    empty_values: usize,
+}
+
+#[derive(Clone, Debug)]
+enum Node {
+    Empty {
+        // Whether this is the root of the context it's generated in. See
+        // fuzz_with_root for why that matters.
+        root: bool,
+    },
+
+    Ref {
+        definition: usize,
+        nullable: bool,
+    },
+
+    Type {
+        type_: Type,
+
+        // The schema's fuzzPattern and fuzzHint, and the generator for them
+        // given the configuration, if it has one.
+        pattern: Option<String>,
+        hint: Option<String>,
+        generator: Option<SharedHint>,
+
+        nullable: bool,
+    },
+
+    Enum {
+        values: Vec<String>,
+        nullable: bool,
+    },
+
+    Elements {
+        elements: usize,
+        nullable: bool,
+    },
+
+    Properties {
+        // Each of these is sorted by property name.
+        required: Vec<(String, usize)>,
+        optional: Vec<(String, usize)>,
+
+        // The names of all of the properties, if additional properties are
+        // allowed.
+        additional: Option<DefinedProperties>,
+
+        nullable: bool,
+    },
+
+    Values {
+        values: usize,
+        nullable: bool,
+    },
+
+    Discriminator {
+        discriminator: String,
+        variants: Vec<(String, usize)>,
+
+        // The indices, in variants, of the variants that are quickest to finish
+        // generating. These are the only ones generated once recursion is
+        // exhausted.
+        shallowest: Vec<usize>,
+
+        nullable: bool,
+    },
+}
+
+// The counterpart of Context for a CompiledFuzzer.
+struct State {
+    refs: Vec<usize>,
+    recursion_depth: usize,
+}
+
+impl CompiledFuzzer {
+    /// Prepares a schema for generating values, using the default
+    /// configuration.
+    ///
+    /// The schema should be valid. In particular, this panics if the schema
+    /// has a `ref` to a definition that doesn't exist.
+    pub fn new(schema: &Schema) -> Self {
+        let definition_ids = schema
+            .definitions()
+            .keys()
+            .enumerate()
+            .map(|(id, name)| (name.as_str(), id))
+            .collect();
+
+        let mut compiler = Compiler {
+            definition_ids,
+            min_depths: analysis::compute_min_depths(schema),
+            nodes: Vec::new(),
+        };
+
+        compiler.compile(schema, true);
+
+        let definitions = schema
+            .definitions()
+            .values()
+            .map(|definition| compiler.compile(definition, false))
+            .collect();
+
+        let empty = compiler.push(Node::Empty { root: true });
+        let empty_member = compiler.push(Node::Empty { root: false });
+        let empty_elements = compiler.push(Node::Elements {
+            elements: empty_member,
+            nullable: false,
+        });
+        let empty_values = compiler.push(Node::Values {
+            values: empty_member,
+            nullable: false,
+        });
+
+        Self {
+            config: FuzzConfig::default(),
+            nodes: compiler.nodes,
+            definitions,
+            empty,
+            empty_elements,
+            empty_values,
+        }
+        .with_config(FuzzConfig::default())
+    }
+
+    /// Sets the configuration to generate values with.
+    pub fn with_config(mut self, config: FuzzConfig) -> Self {
+        // Hints are looked up in the configuration's registry, and patterns
+        // depend on the configured string length, so they have to be looked up
+        // again whenever the configuration changes.
+        for node in &mut self.nodes {
+            if let Node::Type {
+                ref pattern,
+                ref hint,
+                ref mut generator,
+                ..
+            } = node
+            {
+                *generator = string_generator(&config, pattern.as_deref(), hint.as_deref());
+            }
+        }
+
+        self.config = config;
+        self
+    }
+
+    /// Generates a single random JSON value satisfying the schema.
+    ///
+    /// See [`fuzz`](crate::fuzz) for details.
+    pub fn fuzz<R: rand::Rng>(&self, rng: &mut R) -> Value {
+        self.fuzz_node(&mut State::new(), rng, 0)
+    }
+
+    // The counterpart of fuzz_with_root. Each case makes the same random
+    // choices, in the same order, as the same case in fuzz_with_root.
+    fn fuzz_node<R: rand::Rng>(&self, state: &mut State, rng: &mut R, node: usize) -> Value {
+        let config = &self.config;
+        let exhausted = state.recursion_depth > config.max_recursion_depth;
+        let gen_null = |rng: &mut R, nullable: bool| {
+            nullable && (exhausted || gen_probability(config, rng, config.null_probability))
+        };
+
+        match self.nodes[node] {
+            Node::Empty { root } => {
+                let cases = if root && !exhausted { 7 } else { 5 };
+                match rng.gen_range(0..cases) {
+                    5 => self.fuzz_node(&mut State::new(), rng, self.empty_elements),
+                    6 => self.fuzz_node(&mut State::new(), rng, self.empty_values),
+                    case => fuzz_empty_primitive(config, rng, case),
+                }
+            }
+
+            Node::Ref {
+                definition,
+                nullable,
+            } => {
+                if gen_null(rng, nullable) {
+                    return Value::Null;
+                }
+
+                let recursive = state.refs.contains(&definition);
+                state.refs.push(definition);
+                if recursive {
+                    state.recursion_depth += 1;
+                }
+
+                let value = self.fuzz_node(state, rng, self.definitions[definition]);
+
+                state.refs.pop();
+                if recursive {
+                    state.recursion_depth -= 1;
+                }
+
+                value
+            }
+
+            Node::Type {
+                ref type_,
+                ref generator,
+                nullable,
+                ..
+            } => {
+                if gen_null(rng, nullable) {
+                    return Value::Null;
+                }
+
+                let hint = generator.as_ref().map(SharedHint::get);
+                fuzz_type_with_hint(config, rng, type_, hint)
+            }
+
+            Node::Enum {
+                ref values,
+                nullable,
+            } => {
+                if gen_null(rng, nullable) {
+                    return Value::Null;
+                }
+
+                values.iter().choose(rng).unwrap().clone().into()
+            }
+
+            Node::Elements { elements, nullable } => {
+                if gen_null(rng, nullable) {
+                    return Value::Null;
+                }
+
+                let len = if exhausted {
+                    0
+                } else {
+                    gen_length(rng, &config.elements_length)
+                };
+
+                (0..len)
+                    .map(|_| self.fuzz_node(state, rng, elements))
+                    .collect::<Vec<_>>()
+                    .into()
+            }
+
+            Node::Properties {
+                ref required,
+                ref optional,
+                ref additional,
+                nullable,
+            } => {
+                if gen_null(rng, nullable) {
+                    return Value::Null;
+                }
+
+                let mut members = serde_json::Map::new();
+
+                for (k, node) in required {
+                    members.insert(k.clone(), self.fuzz_node(state, rng, *node));
+                }
+
+                for (k, node) in optional {
+                    if exhausted
+                        || gen_probability(config, rng, 1.0 - config.optional_property_probability)
+                    {
+                        continue;
+                    }
+
+                    members.insert(k.clone(), self.fuzz_node(state, rng, *node));
+                }
+
+                if let Some(defined) = additional {
+                    if !exhausted {
+                        for _ in 0..gen_length(rng, &config.additional_properties_length) {
+                            if let Some(key) = strings::fuzz_additional_key(config, rng, defined) {
+                                let value = self.fuzz_node(&mut State::new(), rng, self.empty);
+                                members.insert(key, value);
+                            }
+                        }
+                    }
+                }
+
+                members.into()
+            }
+
+            Node::Values { values, nullable } => {
+                if gen_null(rng, nullable) {
+                    return Value::Null;
+                }
+
+                let len = if exhausted {
+                    0
+                } else {
+                    gen_length(rng, &config.values_length)
+                };
+
+                (0..len)
+                    .map(|_| {
+                        (
+                            strings::fuzz_string(config, rng),
+                            self.fuzz_node(state, rng, values),
+                        )
+                    })
+                    .collect::<serde_json::Map<String, Value>>()
+                    .into()
+            }
+
+            Node::Discriminator {
+                ref discriminator,
+                ref variants,
+                ref shallowest,
+                nullable,
+            } => {
+                if gen_null(rng, nullable) {
+                    return Value::Null;
+                }
+
+                let (discriminator_value, variant) = if exhausted {
+                    &variants[*shallowest.choose(rng).unwrap()]
+                } else {
+                    variants.choose(rng).unwrap()
+                };
+
+                let mut obj = self.fuzz_node(state, rng, *variant);
+                obj.as_object_mut()
+                    .unwrap()
+                    .insert(discriminator.clone(), discriminator_value.clone().into());
+                obj
+            }
+        }
+    }
+}
+
+impl State {
+    fn new() -> Self {
+        Self {
+            refs: Vec::new(),
+            recursion_depth: 0,
+        }
+    }
+}
+
+struct Compiler<'a> {
+    definition_ids: BTreeMap<&'a str, usize>,
+    min_depths: MinDepths,
+    nodes: Vec<Node>,
+}
+
+impl<'a> Compiler<'a> {
+    fn push(&mut self, node: Node) -> usize {
+        self.nodes.push(node);
+        self.nodes.len() - 1
+    }
+
+    // Compiles a schema, and any schemas it contains, returning its index.
+    fn compile(&mut self, schema: &Schema, root: bool) -> usize {
+        // Reserve this schema's index before compiling the schemas it
+        // contains, so that the root schema comes first.
+        let index = self.push(Node::Empty { root });
+
+        let node = match schema {
+            Schema::Empty { .. } => return index,
+
+            Schema::Ref {
+                ref ref_, nullable, ..
+            } => Node::Ref {
+                definition: *self
+                    .definition_ids
+                    .get(ref_.as_str())
+                    .unwrap_or_else(|| panic!("ref to a missing definition: {}", ref_)),
+                nullable: *nullable,
+            },
+
+            Schema::Type {
+                ref metadata,
+                ref type_,
+                nullable,
+                ..
+            } => Node::Type {
+                type_: type_.clone(),
+                pattern: string_metadata(type_, metadata, METADATA_KEY_FUZZ_PATTERN)
+                    .map(str::to_owned),
+                hint: string_metadata(type_, metadata, METADATA_KEY_FUZZ_HINT).map(str::to_owned),
+                generator: None,
+                nullable: *nullable,
+            },
+
+            Schema::Enum {
+                ref enum_,
+                nullable,
+                ..
+            } => Node::Enum {
+                values: enum_.iter().cloned().collect(),
+                nullable: *nullable,
+            },
+
+            Schema::Elements {
+                ref elements,
+                nullable,
+                ..
+            } => Node::Elements {
+                elements: self.compile(elements, false),
+                nullable: *nullable,
+            },
+
+            Schema::Properties {
+                ref properties,
+                ref optional_properties,
+                additional_properties,
+                nullable,
+                ..
+            } => Node::Properties {
+                required: properties
+                    .iter()
+                    .map(|(k, s)| (k.clone(), self.compile(s, false)))
+                    .collect(),
+                optional: optional_properties
+                    .iter()
+                    .map(|(k, s)| (k.clone(), self.compile(s, false)))
+                    .collect(),
+                additional: if *additional_properties {
+                    Some(DefinedProperties::new(
+                        properties.keys().chain(optional_properties.keys()),
+                    ))
+                } else {
+                    None
+                },
+                nullable: *nullable,
+            },
+
+            Schema::Values {
+                ref values,
+                nullable,
+                ..
+            } => Node::Values {
+                values: self.compile(values, false),
+                nullable: *nullable,
+            },
+
+            Schema::Discriminator {
+                ref discriminator,
+                ref mapping,
+                nullable,
+                ..
+            } => {
+                // The same choice of variants as Context::variants makes.
+                let depths: Vec<_> = mapping
+                    .values()
+                    .map(|s| analysis::min_depth(&self.min_depths, s))
+                    .collect();
+                let min_depth = depths.iter().flatten().min().copied();
+
+                Node::Discriminator {
+                    discriminator: discriminator.clone(),
+                    variants: mapping
+                        .iter()
+                        .map(|(k, s)| (k.clone(), self.compile(s, false)))
+                        .collect(),
+                    shallowest: (0..depths.len())
+                        .filter(|&i| depths[i] == min_depth)
+                        .collect(),
+                    nullable: *nullable,
+                }
+            }
+        };
+
+        self.nodes[index] = node;
+        index
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{assert_matches_fuzz, parse_schema, recursive_schema};
+    use crate::CharClass;
+    use serde_json::json;
+
+    #[test]
+    fn test_compiled_fuzzer() {
+        let hints = parse_schema(json!({
+            "elements": {
+                "properties": {
+                    "email": {
+                        "type": "string",
+                        "metadata": { "fuzzHint": "en_us/internet/email" },
+                    },
+                    "id": {
+                        "type": "string",
+                        "metadata": { "fuzzPattern": "^[a-f0-9]{4}(-[a-f0-9]{4})*$" },
+                    },
+                },
+            },
+        }));
+        let schemas = [parse_schema(json!({})), recursive_schema(), hints];
+
+        let configs = [
+            FuzzConfig::new(),
+            FuzzConfig::new()
+                .with_max_recursion_depth(0)
+                .with_additional_properties_length(3..=3)
+                .with_string_length(0..=1),
+            FuzzConfig::new().with_hint("en_us/internet/email", |_: &mut dyn rand::RngCore| {
+                "user@example.com".to_owned()
+            }),
+            FuzzConfig::new().with_hints(crate::HintRegistry::empty()),
+        ];
+
+        for schema in &schemas {
+            for config in &configs {
+                let fuzzer = CompiledFuzzer::new(schema).with_config(config.clone());
+                assert_matches_fuzz(schema, config, 300, |v| v, |rng| fuzzer.fuzz(rng));
+            }
+        }
+    }
+
+    #[test]
+    fn test_compiled_additional_properties() {
+        // The defined properties are worked out once, when the schema is
+        // compiled, and must still include the optional ones, folded the same
+        // way fuzz folds them.
+        let schema = parse_schema(json!({
+            "properties": {
+                "k": { "type": "uint8" },
+            },
+            "optionalProperties": {
+                "s": { "type": "uint8" },
+            },
+            "additionalProperties": true,
+        }));
+
+        let config = FuzzConfig::new()
+            .with_string_length(1..=1)
+            .with_additional_properties_length(7..=7)
+            .with_optional_property_probability(0.0)
+            .with_char_classes(vec![CharClass::PrintableAscii, CharClass::Bmp]);
+
+        let fuzzer = CompiledFuzzer::new(&schema).with_config(config.clone());
+        assert_matches_fuzz(
+            &schema,
+            &config,
+            1000,
+            |v| v,
+            |rng| {
+                let instance = fuzzer.fuzz(rng);
+                for key in ["K", "S", "\u{212a}", "\u{17f}"] {
+                    assert!(instance.get(key).is_none(), "{}", instance);
+                }
+
+                instance
+            },
+        );
+    }
+}
@@ -0,0 +1,368 @@
+use crate::{
+    timestamps, CharClass, FloatDistribution, HintGenerator, HintRegistry, NumberStrategy,
+};
+use chrono::{DateTime, TimeZone, Utc};
+use rand::Rng;
+use std::convert::TryFrom;
+use std::ops::RangeInclusive;
+
+/// Options you can pass to [`fuzz_with_config()`](crate::fuzz_with_config).
+///
+/// The default configuration is the one used by [`fuzz()`](crate::fuzz).
+#[derive(Clone, Debug, PartialEq)]
+pub struct FuzzConfig {
+    pub(crate) string_length: RangeInclusive<usize>,
+    pub(crate) char_classes: Vec<CharClass>,
+    pub(crate) elements_length: RangeInclusive<usize>,
+    pub(crate) values_length: RangeInclusive<usize>,
+    pub(crate) additional_properties_length: RangeInclusive<usize>,
+    pub(crate) null_probability: f64,
+    pub(crate) optional_property_probability: f64,
+    pub(crate) max_recursion_depth: usize,
+    pub(crate) number_strategy: NumberStrategy,
+    pub(crate) float_distribution: FloatDistribution,
+    pub(crate) timestamp_range: RangeInclusive<DateTime<Utc>>,
+    pub(crate) utc_timestamps: bool,
+    pub(crate) timestamp_precision: usize,
+    pub(crate) timestamp_edges: bool,
+    pub(crate) hints: HintRegistry,
+
+    // Whether even odds are decided like any other probability, so that a
+    // zero word comes up true, rather than the way fuzz() always has, where it
+    // comes up false. strategy() and fuzz_unstructured() make instances
+    // smaller by making their words smaller, which only works if zeroes mean
+    // null and absent optional properties.
+    pub(crate) zero_words_smallest: bool,
+}
+
+impl Default for FuzzConfig {
+    fn default() -> Self {
+        Self {
+            string_length: 0..=7,
+            char_classes: vec![CharClass::PrintableAscii],
+            elements_length: 0..=7,
+            values_length: 0..=7,
+            additional_properties_length: 0..=7,
+            null_probability: 0.5,
+            optional_property_probability: 0.5,
+            max_recursion_depth: 3,
+            number_strategy: NumberStrategy::Uniform,
+            float_distribution: FloatDistribution::Unit,
+            timestamp_range: Utc.timestamp(i32::MIN as i64, 0)..=Utc.timestamp(i32::MAX as i64, 0),
+            utc_timestamps: false,
+            timestamp_precision: 0,
+            timestamp_edges: false,
+            hints: HintRegistry::default(),
+            zero_words_smallest: false,
+        }
+    }
+}
+
+impl FuzzConfig {
+    /// Construct a new set of options with all default values.
+    ///
+    /// Equivalent to [`Default::default()`].
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets how many characters generated strings may have.
+    ///
+    /// This applies to `type: string` values without a recognized `fuzzHint`,
+    /// as well as to the keys generated for `values` and `additionalProperties`.
+    ///
+    /// The default is `0..=7`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `len` is empty.
+    pub fn with_string_length(mut self, len: RangeInclusive<usize>) -> Self {
+        assert!(!len.is_empty(), "string length range is empty");
+        self.string_length = len;
+        self
+    }
+
+    /// Sets the classes of characters that generated strings may contain.
+    ///
+    /// Like [`with_string_length`](Self::with_string_length), this applies to
+    /// `type: string` values without a recognized `fuzzHint`, as well as to
+    /// generated keys. Each character is drawn from one of `classes`, chosen
+    /// at random, so a string may mix characters from several classes.
+    ///
+    /// Many bugs involving strings only show up for text outside of ASCII, or
+    /// for text whose length in bytes, UTF-16 code units, characters, and
+    /// grapheme clusters all differ. Adding classes such as
+    /// [`CharClass::Astral`] or [`CharClass::Combining`] exercises those.
+    ///
+    /// The default is `vec![CharClass::PrintableAscii]`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `classes` is empty.
+    pub fn with_char_classes(mut self, classes: Vec<CharClass>) -> Self {
+        assert!(!classes.is_empty(), "char classes are empty");
+        self.char_classes = classes;
+        self
+    }
+
+    /// Sets how many elements generated arrays (for `elements` schemas) may
+    /// have.
+    ///
+    /// The default is `0..=7`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `len` is empty.
+    pub fn with_elements_length(mut self, len: RangeInclusive<usize>) -> Self {
+        assert!(!len.is_empty(), "elements length range is empty");
+        self.elements_length = len;
+        self
+    }
+
+    /// Sets how many members generated objects for `values` schemas may have.
+    ///
+    /// Because keys are generated at random, the generated object may end up
+    /// with fewer members than were attempted if the same key is generated
+    /// twice.
+    ///
+    /// The default is `0..=7`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `len` is empty.
+    pub fn with_values_length(mut self, len: RangeInclusive<usize>) -> Self {
+        assert!(!len.is_empty(), "values length range is empty");
+        self.values_length = len;
+        self
+    }
+
+    /// Sets how many "extra" properties may be added to objects for schemas
+    /// with `additionalProperties`.
+    ///
+    /// Extra properties which collide with a property the schema defines are
+    /// skipped, so fewer extra properties than were attempted may be generated.
+    ///
+    /// The default is `0..=7`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `len` is empty.
+    pub fn with_additional_properties_length(mut self, len: RangeInclusive<usize>) -> Self {
+        assert!(
+            !len.is_empty(),
+            "additional properties length range is empty"
+        );
+        self.additional_properties_length = len;
+        self
+    }
+
+    /// Sets the probability that `null` is generated for a schema with
+    /// `nullable: true`.
+    ///
+    /// The default is `0.5`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `p` is not between `0.0` and `1.0`, inclusive.
+    pub fn with_null_probability(mut self, p: f64) -> Self {
+        assert!((0.0..=1.0).contains(&p), "null probability out of range");
+        self.null_probability = p;
+        self
+    }
+
+    /// Sets the probability that each of a schema's `optionalProperties` is
+    /// present in a generated object.
+    ///
+    /// The default is `0.5`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `p` is not between `0.0` and `1.0`, inclusive.
+    pub fn with_optional_property_probability(mut self, p: f64) -> Self {
+        assert!(
+            (0.0..=1.0).contains(&p),
+            "optional property probability out of range"
+        );
+        self.optional_property_probability = p;
+        self
+    }
+
+    /// Sets how deeply recursive definitions may be followed before generation
+    /// starts steering towards the smallest possible instances.
+    ///
+    /// A schema can describe a recursive data structure, such as a tree, using
+    /// a definition that (directly or indirectly) refers to itself. Every time
+    /// a `ref` is followed to a definition that is already being generated,
+    /// that counts as one level of recursion.
+    ///
+    /// Once there have been more than `max_recursion_depth` levels of
+    /// recursion, the rest of that part of the instance is generated as small
+    /// as possible: nullable values are `null`, arrays and `values` objects are
+    /// empty, optional and additional properties are omitted, and
+    /// `discriminator` schemas use whichever variant has the smallest
+    /// instances. This guarantees that generation finishes for any schema
+    /// that has a finite instance.
+    ///
+    /// The default is `3`.
+    pub fn with_max_recursion_depth(mut self, max_recursion_depth: usize) -> Self {
+        self.max_recursion_depth = max_recursion_depth;
+        self
+    }
+
+    /// Sets how numbers, both integers and floats, are generated.
+    ///
+    /// Many bugs involving numbers only show up for a few specific values,
+    /// such as the largest value a type can hold, or negative zero. Use
+    /// [`NumberStrategy::Edges`] to generate such values much more often.
+    ///
+    /// The default is [`NumberStrategy::Uniform`].
+    pub fn with_number_strategy(mut self, number_strategy: NumberStrategy) -> Self {
+        self.number_strategy = number_strategy;
+        self
+    }
+
+    /// Sets how `float32` and `float64` values are distributed.
+    ///
+    /// By default, floats are between zero and one, which never exercises
+    /// negative numbers, large numbers, or tiny numbers. Use
+    /// [`FloatDistribution::LogUniform`] or [`FloatDistribution::Bits`] to
+    /// generate floats across their whole finite range.
+    ///
+    /// The default is [`FloatDistribution::Unit`].
+    pub fn with_float_distribution(mut self, float_distribution: FloatDistribution) -> Self {
+        self.float_distribution = float_distribution;
+        self
+    }
+
+    /// Sets the range of instants that generated timestamps may represent.
+    ///
+    /// The default is the range of 32-bit Unix times, `1901-12-13T20:45:52Z`
+    /// through `2038-01-19T03:14:07Z`.
+    ///
+    /// Fractional seconds beyond the precision set by
+    /// [`with_timestamp_precision`](Self::with_timestamp_precision) are
+    /// truncated, so if `range.start()` itself has such fractional seconds,
+    /// a timestamp may be slightly earlier than it. The edge cases generated by
+    /// [`with_timestamp_edges`](Self::with_timestamp_edges) ignore this range
+    /// entirely.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `range` is empty, or if it includes instants before
+    /// `0001-01-01T00:00:00Z` or after `9999-12-31T23:59:59.999999999Z`, which
+    /// RFC3339 cannot represent.
+    pub fn with_timestamp_range(mut self, range: RangeInclusive<DateTime<Utc>>) -> Self {
+        assert!(!range.is_empty(), "timestamp range is empty");
+        assert!(
+            *range.start() >= timestamps::min_timestamp()
+                && *range.end() <= timestamps::max_timestamp(),
+            "timestamp range out of range"
+        );
+        self.timestamp_range = range;
+        self
+    }
+
+    /// Sets whether generated timestamps are always in UTC, written with a `Z`
+    /// suffix.
+    ///
+    /// Otherwise, timestamps are written with a random offset from UTC of at
+    /// most fourteen hours, which is the most that .NET's `DateTimeOffset`
+    /// supports.
+    ///
+    /// The default is `false`.
+    pub fn with_utc_timestamps(mut self, utc_timestamps: bool) -> Self {
+        self.utc_timestamps = utc_timestamps;
+        self
+    }
+
+    /// Sets how many digits of fractional seconds generated timestamps have.
+    ///
+    /// For instance, a precision of `3` generates timestamps like
+    /// `2020-01-02T03:04:05.678Z`. A precision of `0` omits fractional seconds
+    /// entirely.
+    ///
+    /// The default is `0`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `precision` is greater than `9`, the most digits that can be
+    /// meaningful with nanosecond resolution.
+    pub fn with_timestamp_precision(mut self, precision: usize) -> Self {
+        assert!(precision <= 9, "timestamp precision out of range");
+        self.timestamp_precision = precision;
+        self
+    }
+
+    /// Sets whether to generate "edge case" timestamps half the time.
+    ///
+    /// The edge cases are the first and last instants RFC3339 can represent
+    /// (`0001-01-01T00:00:00Z` and `9999-12-31T23:59:59Z`), leap days, real
+    /// leap seconds like `2016-12-31T23:59:60Z`, and timestamps that use the
+    /// lowercase `t` and `z` separators RFC3339 permits. Edge cases are always
+    /// in UTC, and are not limited by
+    /// [`with_timestamp_range`](Self::with_timestamp_range).
+    ///
+    /// The default is `false`.
+    pub fn with_timestamp_edges(mut self, timestamp_edges: bool) -> Self {
+        self.timestamp_edges = timestamp_edges;
+        self
+    }
+
+    /// Sets the registry that `fuzzHint`s are looked up in.
+    ///
+    /// Strings for schemas whose `fuzzHint` isn't in `hints` are generated as
+    /// if they had no hint at all. See [`HintRegistry`] for how to add hints of
+    /// your own.
+    ///
+    /// The default is [`HintRegistry::new()`], which has all the hints listed
+    /// in [`fuzz`](crate::fuzz).
+    pub fn with_hints(mut self, hints: HintRegistry) -> Self {
+        self.hints = hints;
+        self
+    }
+
+    /// Adds a single hint to the registry that `fuzzHint`s are looked up in,
+    /// replacing any existing hint with the same name.
+    ///
+    /// This is shorthand for [`HintRegistry::register`] on the registry set
+    /// with [`with_hints`](Self::with_hints).
+    pub fn with_hint<G: HintGenerator + 'static>(
+        mut self,
+        name: impl Into<String>,
+        generator: G,
+    ) -> Self {
+        self.hints.register(name, generator);
+        self
+    }
+
+    // See zero_words_smallest.
+    pub(crate) fn with_zero_words_smallest(mut self) -> Self {
+        self.zero_words_smallest = true;
+        self
+    }
+}
+
+// Returns true with probability p.
+//
+// Even odds are decided with rng.gen(), which uses a single word, rather than
+// with rng.gen_bool(0.5), which uses two. That's how fuzz() decided them
+// before they could be configured, so the default configuration still
+// generates the same values from the same seed.
+pub(crate) fn gen_probability<R: Rng + ?Sized>(config: &FuzzConfig, rng: &mut R, p: f64) -> bool {
+    if p == 0.5 && !config.zero_words_smallest {
+        rng.gen()
+    } else {
+        rng.gen_bool(p)
+    }
+}
+
+// Chooses a length from range.
+//
+// For the same reason as in gen_probability, lengths that fit in a u8 are
+// chosen as a u8, which rand samples differently from a usize.
+pub(crate) fn gen_length<R: Rng + ?Sized>(rng: &mut R, range: &RangeInclusive<usize>) -> usize {
+    match u8::try_from(*range.end()) {
+        Ok(end) => rng.gen_range(*range.start() as u8..=end) as usize,
+        Err(_) => rng.gen_range(range.clone()),
+    }
+}
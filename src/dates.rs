@@ -0,0 +1,189 @@
+use crate::hints::{Generator, HintGenerator};
+use crate::timestamps::fuzz_local_instant;
+use chrono::format::{Item, StrftimeItems};
+use rand::{Rng, RngCore};
+use std::fmt::Write;
+
+// Generators for dates, times, and durations written as strings other than
+// RFC3339 timestamps, for schemas that can't use `type: timestamp`. Dates and
+// times come from the same range as the timestamps fuzz generates by default.
+
+// The hints for dates, times, and durations. When adding to this list, also add
+// to the list in the documentation of fuzz.
+pub(crate) const DATE_HINTS: &[(&str, Generator)] = &[
+    ("datetime/date", date),
+    ("datetime/duration", duration),
+    ("datetime/time", time),
+    ("datetime/unix_millis", unix_millis),
+    ("datetime/unix_seconds", unix_seconds),
+];
+
+// The prefix of hints with a custom strftime pattern, like "strftime:%d/%m/%Y".
+pub(crate) const STRFTIME_PREFIX: &str = "strftime:";
+
+// An RFC3339 full-date, like "2024-02-29".
+fn date(rng: &mut dyn RngCore) -> String {
+    fuzz_local_instant(rng).format("%Y-%m-%d").to_string()
+}
+
+// An RFC3339 partial-time, like "23:59:01", without fractional seconds.
+fn time(rng: &mut dyn RngCore) -> String {
+    fuzz_local_instant(rng).format("%H:%M:%S").to_string()
+}
+
+fn unix_seconds(rng: &mut dyn RngCore) -> String {
+    fuzz_local_instant(rng).timestamp().to_string()
+}
+
+fn unix_millis(rng: &mut dyn RngCore) -> String {
+    fuzz_local_instant(rng).timestamp_millis().to_string()
+}
+
+// An ISO 8601 duration, like "P1Y2M3DT4H5M6S", "PT0.5S", or "P3W". Each part
+// is included or left out at random, but there's always at least one part.
+fn duration(rng: &mut dyn RngCore) -> String {
+    if rng.gen_ratio(1, 8) {
+        return format!("P{}W", rng.gen_range(0..=52));
+    }
+
+    let maxes = [10, 11, 30, 23, 59, 59];
+    let mut parts = [None; 6];
+    for (part, max) in parts.iter_mut().zip(&maxes) {
+        if rng.gen() {
+            *part = Some(rng.gen_range(0..=*max));
+        }
+    }
+
+    if parts.iter().all(Option::is_none) {
+        let i = rng.gen_range(0..parts.len());
+        parts[i] = Some(rng.gen_range(0..=maxes[i]));
+    }
+
+    let mut out = "P".to_owned();
+    for (part, unit) in parts[..3].iter().zip(&['Y', 'M', 'D']) {
+        if let Some(n) = part {
+            write!(out, "{}{}", n, unit).unwrap();
+        }
+    }
+
+    if parts[3..].iter().any(Option::is_some) {
+        out.push('T');
+    }
+
+    for (part, unit) in parts[3..5].iter().zip(&['H', 'M']) {
+        if let Some(n) = part {
+            write!(out, "{}{}", n, unit).unwrap();
+        }
+    }
+
+    if let Some(secs) = parts[5] {
+        if rng.gen_ratio(1, 4) {
+            write!(out, "{}.{:03}S", secs, rng.gen_range(0..1000)).unwrap();
+        } else {
+            write!(out, "{}S", secs).unwrap();
+        }
+    }
+
+    out
+}
+
+// Formats dates and times with a strftime pattern, as understood by chrono.
+pub(crate) struct Strftime(String);
+
+impl Strftime {
+    // Parses a hint of the form "strftime:PATTERN". Returns None if the hint
+    // isn't of that form, or if chrono doesn't understand the pattern.
+    pub(crate) fn parse(hint: &str) -> Option<Self> {
+        let pattern = hint.strip_prefix(STRFTIME_PREFIX)?;
+        if StrftimeItems::new(pattern).any(|item| item == Item::Error) {
+            return None;
+        }
+
+        Some(Self(pattern.to_owned()))
+    }
+}
+
+impl HintGenerator for Strftime {
+    fn generate(&self, rng: &mut dyn RngCore) -> String {
+        fuzz_local_instant(rng).format(&self.0).to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{DateTime, NaiveDate, NaiveDateTime, NaiveTime};
+    use rand::SeedableRng;
+
+    #[test]
+    fn test_date_hints() {
+        let min = DateTime::parse_from_rfc3339("1901-12-13T20:45:52Z").unwrap();
+        let max = DateTime::parse_from_rfc3339("2038-01-19T03:14:07Z").unwrap();
+
+        let mut rng = rand_pcg::Pcg32::seed_from_u64(8927);
+        for _ in 0..1000 {
+            let date = NaiveDate::parse_from_str(&date(&mut rng), "%Y-%m-%d").unwrap();
+            assert!((1901..=2038).contains(&chrono::Datelike::year(&date)));
+
+            NaiveTime::parse_from_str(&time(&mut rng), "%H:%M:%S").unwrap();
+
+            let secs: i64 = unix_seconds(&mut rng).parse().unwrap();
+            assert!(min.timestamp() <= secs && secs <= max.timestamp());
+
+            let millis: i64 = unix_millis(&mut rng).parse().unwrap();
+            assert!(min.timestamp_millis() <= millis && millis <= max.timestamp_millis());
+
+            assert_duration(&duration(&mut rng));
+        }
+    }
+
+    #[test]
+    fn test_strftime() {
+        assert!(Strftime::parse("%d/%m/%Y").is_none());
+        assert!(Strftime::parse("strftime:%d/%m/%Y %Q").is_none());
+
+        let strftime = Strftime::parse("strftime:%d/%m/%Y %H:%M").unwrap();
+        let mut rng = rand_pcg::Pcg32::seed_from_u64(8927);
+        for _ in 0..100 {
+            let s = strftime.generate(&mut rng);
+            NaiveDateTime::parse_from_str(&s, "%d/%m/%Y %H:%M").unwrap();
+        }
+    }
+
+    fn assert_duration(s: &str) {
+        let rest = s.strip_prefix('P').unwrap();
+        if let Some(weeks) = rest.strip_suffix('W') {
+            weeks.parse::<u32>().unwrap();
+            return;
+        }
+
+        let (date, time) = match rest.split_once('T') {
+            Some((date, time)) => {
+                assert!(!time.is_empty(), "{}", s);
+                (date, time)
+            }
+            None => (rest, ""),
+        };
+        assert!(!date.is_empty() || !time.is_empty(), "{}", s);
+
+        assert_parts(s, date, &['Y', 'M', 'D']);
+        assert_parts(s, time, &['H', 'M', 'S']);
+    }
+
+    // Checks that parts are numbers followed by units, in the order given.
+    fn assert_parts(s: &str, mut parts: &str, units: &[char]) {
+        for unit in units {
+            if let Some((n, rest)) = parts.split_once(*unit) {
+                if *unit == 'S' {
+                    n.parse::<f64>().unwrap();
+                } else {
+                    n.parse::<u32>().unwrap();
+                }
+
+                parts = rest;
+            }
+        }
+
+        assert!(parts.is_empty(), "{}", s);
+    }
+}
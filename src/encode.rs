@@ -0,0 +1,949 @@
+use crate::words::Replay;
+use crate::{
+    empty_elements_schema, empty_schema, empty_values_schema, fuzz_with_config, fuzz_with_root,
+    numbers, string_generator, string_metadata, strings, timestamps, Context, FuzzConfig,
+    METADATA_KEY_FUZZ_HINT, METADATA_KEY_FUZZ_PATTERN,
+};
+use jtd::{Schema, Type};
+use serde_json::Value;
+use std::ops::RangeInclusive;
+use thiserror::Error;
+
+/// Indicates that an instance could not be encoded.
+///
+/// This happens if the instance isn't valid against the schema, or if it is
+/// valid but [`fuzz_with_config`] would never generate it with the given
+/// configuration. For instance, a string with ten characters can't be encoded
+/// with the default configuration, because generated strings have at most
+/// seven characters.
+#[derive(Clone, Debug, PartialEq, Eq, Error)]
+#[error("cannot encode instance at {instance_path:?}: {reason}")]
+pub struct EncodeError {
+    /// A path to the part of the instance that could not be encoded.
+    pub instance_path: Vec<String>,
+
+    /// Why that part of the instance could not be encoded.
+    pub reason: String,
+}
+
+/// Encodes an instance of a schema as the bytes that would generate it.
+///
+#[cfg_attr(
+    feature = "arbitrary",
+    doc = "This is the inverse of [`fuzz_unstructured`](crate::fuzz_unstructured):"
+)]
+#[cfg_attr(
+    not(feature = "arbitrary"),
+    doc = "This is the inverse of `fuzz_unstructured`, from the `arbitrary` feature:"
+)]
+/// decoding the returned bytes, with the same schema, gives back `instance`.
+/// It's useful for turning real-world data into the seed corpus of a
+/// coverage-guided fuzzer, so that the fuzzer starts out from realistic inputs.
+///
+/// Trailing zero bytes are left out, since a fuzzer reading past the end of its
+/// input treats it as if it were padded with zeroes anyway.
+///
+/// ```
+/// use serde_json::json;
+///
+/// let schema = jtd::Schema::from_serde_schema(serde_json::from_value(json!({
+///     "elements": { "type": "uint8" }
+/// })).unwrap()).unwrap();
+///
+/// assert_eq!(
+///     jtd_fuzz::encode(&schema, &json!([1, 2])).unwrap(),
+///     vec![0, 0, 0, 64, 1, 0, 0, 0, 2],
+/// );
+/// ```
+///
+/// # Limitations
+///
+/// Not every valid instance can be encoded, because some can never be
+/// generated. Apart from instances that fall outside of the configured
+/// lengths, ranges, and character classes, this function returns an
+/// [`EncodeError`] for:
+///
+/// * Strings for schemas with a recognized `fuzzHint` or a usable `fuzzPattern`.
+///
+/// * Floats that aren't a multiple of 2<sup>-53</sup> (or 2<sup>-24</sup> for
+///   `float32`) between zero and one, or any floats at all with
+///   [`FloatDistribution::LogUniform`](crate::FloatDistribution::LogUniform).
+///   With [`FloatDistribution::Bits`](crate::FloatDistribution::Bits), all
+///   finite floats can be encoded.
+///
+/// * Timestamps that could only be generated as edge cases, such as leap
+///   seconds and lowercase timestamps.
+///
+/// * Instances nested more deeply than
+///   [`FuzzConfig::with_max_recursion_depth`] allows.
+///
+/// Numbers are compared by value, so `1` and `1.0` are considered the same.
+pub fn encode(schema: &Schema, instance: &Value) -> Result<Vec<u8>, EncodeError> {
+    encode_with_config(schema, &FuzzConfig::default(), instance)
+}
+
+/// Encodes an instance of a schema as the bytes that would generate it, with a
+/// custom configuration.
+///
+/// See [`encode`] for details. The bytes only decode back to `instance` when
+/// decoded with the same `config`.
+pub fn encode_with_config(
+    schema: &Schema,
+    config: &FuzzConfig,
+    instance: &Value,
+) -> Result<Vec<u8>, EncodeError> {
+    let errors = jtd::validate(schema, instance, Default::default())
+        .expect("validation only fails when a max depth is set");
+
+    if let Some(error) = errors.first() {
+        return Err(EncodeError {
+            instance_path: error
+                .instance_path
+                .iter()
+                .map(|token| token.to_string())
+                .collect(),
+            reason: "instance is not valid against the schema".into(),
+        });
+    }
+
+    // These are the bytes fuzz_unstructured_with_config() would use, so they
+    // are decided the way it decides them.
+    let config = &config.clone().with_zero_words_smallest();
+
+    let mut enc = Encoder::new();
+    encode_with_root(
+        &mut Context::new(config, schema),
+        &mut enc,
+        &mut vec![],
+        schema,
+        instance,
+    )?;
+
+    // Every part of the instance was checked as it was encoded, but check the
+    // whole thing too, in case the parts didn't line up.
+    let mut rng = Replay {
+        words: enc.words.clone(),
+        position: 0,
+    };
+
+    if !equivalent(&fuzz_with_config(schema, config, &mut rng), instance) {
+        return Err(EncodeError {
+            instance_path: vec![],
+            reason: "instance would not be generated with this configuration".into(),
+        });
+    }
+
+    let mut bytes: Vec<u8> = enc.words.iter().flat_map(|w| w.to_le_bytes()).collect();
+    while bytes.last() == Some(&0) {
+        bytes.pop();
+    }
+
+    Ok(bytes)
+}
+
+// Writes the words that would make fuzz_with_root generate instance. Each case
+// here mirrors the random choices made by the same case in fuzz_with_root.
+fn encode_with_root<'a>(
+    cx: &mut Context<'a>,
+    enc: &mut Encoder,
+    path: &mut Vec<String>,
+    schema: &'a Schema,
+    instance: &Value,
+) -> Result<(), EncodeError> {
+    let config = cx.config;
+    let start = enc.words.len();
+
+    match schema {
+        Schema::Empty { .. } => {
+            let recursive = std::ptr::eq(cx.root, schema) && !cx.exhausted();
+            let case = match instance {
+                Value::Null => 0,
+                Value::Bool(_) => 1,
+                Value::Number(n) if n.as_u64().is_some_and(|n| n <= u8::MAX as u64) => 2,
+                Value::Number(_) => 3,
+                Value::String(_) => 4,
+                Value::Array(_) if recursive => 5,
+                Value::Object(_) if recursive => 6,
+                _ => {
+                    return Err(unreachable(
+                        path,
+                        "arrays and objects are only generated for empty schemas at the root",
+                    ))
+                }
+            };
+
+            enc.range_u32(case, if recursive { 6 } else { 4 });
+
+            match instance {
+                Value::Null => {}
+                Value::Bool(b) => enc.bool(*b),
+                Value::Number(n) if case == 2 => {
+                    numbers::encode_int(config, enc, n.as_i64().unwrap())
+                }
+                Value::Number(n) => numbers::encode_float64(config, enc, n.as_f64().unwrap())
+                    .map_err(|reason| unreachable(path, reason))?,
+                Value::String(s) => strings::encode_string(config, enc, s)
+                    .map_err(|reason| unreachable(path, reason))?,
+                Value::Array(_) => {
+                    let schema = empty_elements_schema();
+                    let cx = &mut Context::new(config, &schema);
+                    return encode_with_root(cx, enc, path, &schema, instance);
+                }
+                Value::Object(_) => {
+                    let schema = empty_values_schema();
+                    let cx = &mut Context::new(config, &schema);
+                    return encode_with_root(cx, enc, path, &schema, instance);
+                }
+            }
+
+            verify(enc, path, start, instance, |rng| {
+                fuzz_with_root(cx, rng, schema)
+            })
+        }
+
+        Schema::Ref {
+            ref ref_, nullable, ..
+        } => {
+            if encode_null(cx, enc, path, *nullable, instance)? {
+                return Ok(());
+            }
+
+            let recursive = cx.refs.contains(&ref_.as_str());
+            cx.refs.push(ref_);
+            if recursive {
+                cx.recursion_depth += 1;
+            }
+
+            let root = cx.root;
+            let result = encode_with_root(cx, enc, path, &root.definitions()[ref_], instance);
+
+            cx.refs.pop();
+            if recursive {
+                cx.recursion_depth -= 1;
+            }
+
+            result
+        }
+
+        Schema::Type {
+            ref metadata,
+            ref type_,
+            nullable,
+            ..
+        } => {
+            if encode_null(cx, enc, path, *nullable, instance)? {
+                return Ok(());
+            }
+
+            let result = match type_ {
+                Type::Boolean => {
+                    enc.bool(instance.as_bool().unwrap());
+                    Ok(())
+                }
+                Type::Float32 => numbers::encode_float32(config, enc, instance.as_f64().unwrap()),
+                Type::Float64 => numbers::encode_float64(config, enc, instance.as_f64().unwrap()),
+                Type::Int8
+                | Type::Uint8
+                | Type::Int16
+                | Type::Uint16
+                | Type::Int32
+                | Type::Uint32 => {
+                    numbers::encode_int(config, enc, instance.as_i64().unwrap());
+                    Ok(())
+                }
+                Type::String => {
+                    let pattern = string_metadata(type_, metadata, METADATA_KEY_FUZZ_PATTERN);
+                    let hint = string_metadata(type_, metadata, METADATA_KEY_FUZZ_HINT);
+
+                    if string_generator(config, pattern, None).is_some() {
+                        Err("strings with a fuzzPattern cannot be encoded")
+                    } else if string_generator(config, None, hint).is_some() {
+                        Err("strings with a fuzzHint cannot be encoded")
+                    } else {
+                        strings::encode_string(config, enc, instance.as_str().unwrap())
+                    }
+                }
+                Type::Timestamp => {
+                    timestamps::encode_timestamp(config, enc, instance.as_str().unwrap())
+                }
+            };
+
+            result.map_err(|reason| unreachable(path, reason))?;
+            verify(enc, path, start, instance, |rng| {
+                fuzz_with_root(cx, rng, schema)
+            })
+        }
+
+        Schema::Enum {
+            ref enum_,
+            nullable,
+            ..
+        } => {
+            if encode_null(cx, enc, path, *nullable, instance)? {
+                return Ok(());
+            }
+
+            let value = instance.as_str().unwrap();
+            enc.index(enum_.iter().position(|v| v == value).unwrap(), enum_.len());
+
+            verify(enc, path, start, instance, |rng| {
+                fuzz_with_root(cx, rng, schema)
+            })
+        }
+
+        Schema::Elements {
+            ref elements,
+            nullable,
+            ..
+        } => {
+            if encode_null(cx, enc, path, *nullable, instance)? {
+                return Ok(());
+            }
+
+            let instance = instance.as_array().unwrap();
+            encode_len(cx, enc, path, &config.elements_length, instance.len())?;
+
+            for (i, element) in instance.iter().enumerate() {
+                path.push(i.to_string());
+                encode_with_root(cx, enc, path, elements, element)?;
+                path.pop();
+            }
+
+            Ok(())
+        }
+
+        Schema::Properties {
+            ref properties,
+            ref optional_properties,
+            additional_properties,
+            nullable,
+            ..
+        } => {
+            if encode_null(cx, enc, path, *nullable, instance)? {
+                return Ok(());
+            }
+
+            let instance = instance.as_object().unwrap();
+
+            let mut required_keys: Vec<_> = properties.keys().collect();
+            required_keys.sort();
+
+            for k in required_keys {
+                path.push(k.clone());
+                encode_with_root(cx, enc, path, &properties[k], &instance[k])?;
+                path.pop();
+            }
+
+            let mut optional_keys: Vec<_> = optional_properties.keys().collect();
+            optional_keys.sort();
+
+            for k in optional_keys {
+                let value = instance.get(k);
+
+                path.push(k.clone());
+                if cx.exhausted() {
+                    if value.is_some() {
+                        return Err(too_deep(path));
+                    }
+                } else if !enc.gen_bool(
+                    config,
+                    1.0 - config.optional_property_probability,
+                    value.is_none(),
+                ) {
+                    return Err(unreachable(
+                        path,
+                        if value.is_some() {
+                            "optional properties are never generated with this configuration"
+                        } else {
+                            "optional properties are always generated with this configuration"
+                        },
+                    ));
+                }
+
+                if let Some(value) = value {
+                    encode_with_root(cx, enc, path, &optional_properties[k], value)?;
+                }
+                path.pop();
+            }
+
+            let additional: Vec<_> = instance
+                .iter()
+                .filter(|(k, _)| {
+                    !properties.contains_key(*k) && !optional_properties.contains_key(*k)
+                })
+                .collect();
+
+            if *additional_properties {
+                encode_len(
+                    cx,
+                    enc,
+                    path,
+                    &config.additional_properties_length,
+                    additional.len(),
+                )?;
+
+                let defined = strings::DefinedProperties::new(
+                    properties.keys().chain(optional_properties.keys()),
+                );
+
+                for (k, v) in additional {
+                    path.push(k.clone());
+                    if defined.collides(k) {
+                        return Err(unreachable(
+                            path,
+                            "additional properties never collide with a defined property",
+                        ));
+                    }
+
+                    encode_key(config, enc, path, k)?;
+
+                    let schema = empty_schema();
+                    encode_with_root(&mut Context::new(config, &schema), enc, path, &schema, v)?;
+                    path.pop();
+                }
+            }
+
+            Ok(())
+        }
+
+        Schema::Values {
+            ref values,
+            nullable,
+            ..
+        } => {
+            if encode_null(cx, enc, path, *nullable, instance)? {
+                return Ok(());
+            }
+
+            let instance = instance.as_object().unwrap();
+            encode_len(cx, enc, path, &config.values_length, instance.len())?;
+
+            for (k, v) in instance {
+                path.push(k.clone());
+                encode_key(config, enc, path, k)?;
+                encode_with_root(cx, enc, path, values, v)?;
+                path.pop();
+            }
+
+            Ok(())
+        }
+
+        Schema::Discriminator {
+            ref mapping,
+            ref discriminator,
+            nullable,
+            ..
+        } => {
+            if encode_null(cx, enc, path, *nullable, instance)? {
+                return Ok(());
+            }
+
+            let mut instance = instance.as_object().unwrap().clone();
+            let tag = instance.remove(discriminator).unwrap();
+
+            let variants = cx.variants(mapping);
+            let index = match variants.iter().position(|(k, _)| tag == **k) {
+                Some(index) => index,
+                None => {
+                    path.push(discriminator.clone());
+                    return Err(too_deep(path));
+                }
+            };
+
+            enc.index(index, variants.len());
+            encode_with_root(cx, enc, path, variants[index].1, &instance.into())
+        }
+    }
+}
+
+// Writes the words that would make Context::gen_null return whether instance is
+// null. Returns whether it is.
+fn encode_null(
+    cx: &Context,
+    enc: &mut Encoder,
+    path: &[String],
+    nullable: bool,
+    instance: &Value,
+) -> Result<bool, EncodeError> {
+    if !nullable {
+        return Ok(false);
+    }
+
+    let null = instance.is_null();
+    if cx.exhausted() {
+        return if null { Ok(true) } else { Err(too_deep(path)) };
+    }
+
+    if !enc.gen_bool(cx.config, cx.config.null_probability, null) {
+        return Err(unreachable(
+            path,
+            if null {
+                "null is never generated with this configuration"
+            } else {
+                "null is always generated with this configuration"
+            },
+        ));
+    }
+
+    Ok(null)
+}
+
+// Writes the words for the length of an array or object.
+fn encode_len(
+    cx: &Context,
+    enc: &mut Encoder,
+    path: &[String],
+    range: &RangeInclusive<usize>,
+    len: usize,
+) -> Result<(), EncodeError> {
+    if cx.exhausted() {
+        return if len == 0 {
+            Ok(())
+        } else {
+            Err(too_deep(path))
+        };
+    }
+
+    if !range.contains(&len) {
+        return Err(unreachable(
+            path,
+            "length is outside of the configured range",
+        ));
+    }
+
+    enc.length(len, range);
+    Ok(())
+}
+
+// Writes the words for an object key.
+fn encode_key(
+    config: &FuzzConfig,
+    enc: &mut Encoder,
+    path: &[String],
+    key: &str,
+) -> Result<(), EncodeError> {
+    let start = enc.words.len();
+    strings::encode_string(config, enc, key).map_err(|reason| unreachable(path, reason))?;
+    verify(enc, path, start, &key.into(), |rng| {
+        strings::fuzz_string(config, rng).into()
+    })
+}
+
+// Checks that the words written since start make fuzz generate instance, and
+// that it uses exactly those words to do so.
+fn verify(
+    enc: &Encoder,
+    path: &[String],
+    start: usize,
+    instance: &Value,
+    fuzz: impl FnOnce(&mut Replay) -> Value,
+) -> Result<(), EncodeError> {
+    let mut rng = Replay {
+        words: enc.words[start..].to_vec(),
+        position: 0,
+    };
+
+    let value = fuzz(&mut rng);
+    if rng.position != rng.words.len() || !equivalent(&value, instance) {
+        return Err(unreachable(
+            path,
+            "value would not be generated with this configuration",
+        ));
+    }
+
+    Ok(())
+}
+
+// Compares values the way JSON does, where numbers are the same if their values
+// are equal, regardless of whether they were written as integers.
+fn equivalent(a: &Value, b: &Value) -> bool {
+    match (a, b) {
+        (Value::Number(a), Value::Number(b)) => a.as_f64() == b.as_f64(),
+        (Value::Array(a), Value::Array(b)) => {
+            a.len() == b.len() && a.iter().zip(b).all(|(a, b)| equivalent(a, b))
+        }
+        (Value::Object(a), Value::Object(b)) => {
+            a.len() == b.len()
+                && a.iter()
+                    .all(|(k, a)| b.get(k).is_some_and(|b| equivalent(a, b)))
+        }
+        _ => a == b,
+    }
+}
+
+fn unreachable(path: &[String], reason: &str) -> EncodeError {
+    EncodeError {
+        instance_path: path.to_vec(),
+        reason: reason.into(),
+    }
+}
+
+fn too_deep(path: &[String]) -> EncodeError {
+    unreachable(
+        path,
+        "instance is nested too deeply for the configured max recursion depth",
+    )
+}
+
+/// The words that, when used as random numbers, generate a given instance.
+///
+/// Each of its methods undoes one of the ways rand turns random words into a
+/// value, writing the words that make rand produce a given value.
+pub(crate) struct Encoder {
+    pub(crate) words: Vec<u32>,
+}
+
+impl Encoder {
+    pub(crate) fn new() -> Self {
+        Self { words: Vec::new() }
+    }
+
+    /// The inverse of `rng.next_u32()`, or `rng.gen()` for 32-bit or smaller
+    /// integers.
+    pub(crate) fn u32(&mut self, n: u32) {
+        self.words.push(n);
+    }
+
+    /// The inverse of `rng.next_u64()`.
+    pub(crate) fn u64(&mut self, n: u64) {
+        self.u32(n as u32);
+        self.u32((n >> 32) as u32);
+    }
+
+    /// The inverse of `rng.gen::<bool>()`, which looks at the top bit.
+    pub(crate) fn bool(&mut self, b: bool) {
+        self.u32(if b { 1 << 31 } else { 0 });
+    }
+
+    /// The inverse of `gen_probability(config, rng, p)`. Returns false if `b`
+    /// is impossible.
+    pub(crate) fn gen_bool(&mut self, config: &FuzzConfig, p: f64, b: bool) -> bool {
+        // Even odds may use rng.gen(). Otherwise, this is rng.gen_bool(p),
+        // which always returns true for a probability of one, without using
+        // any words, and otherwise checks whether a 64-bit word is below
+        // p * 2^64.
+        if p == 0.5 && !config.zero_words_smallest {
+            self.bool(b);
+            return true;
+        }
+
+        if p == 1.0 {
+            return b;
+        }
+
+        if b && (p * 2.0f64.powi(64)) as u64 == 0 {
+            return false;
+        }
+
+        self.u64(if b { 0 } else { u64::MAX });
+        true
+    }
+
+    /// The inverse of `rng.gen_range(low..=high)` for types that rand samples
+    /// using 32-bit words, given `value - low` and `high - low`.
+    ///
+    /// This includes all integer types up to 32 bits, and `char`, where
+    /// rand skips over the surrogate code points.
+    pub(crate) fn range_u32(&mut self, offset: u32, span: u32) {
+        debug_assert!(offset <= span);
+
+        // rand multiplies the word by the size of the range, and takes the
+        // high half of the result. It rejects some words for which the low
+        // half is too large, so round up to keep the low half small.
+        match span.checked_add(1) {
+            Some(size) => {
+                let size = size as u64;
+                self.u32(((offset as u64) << 32).div_ceil(size) as u32)
+            }
+            None => self.u32(offset),
+        }
+    }
+
+    /// Like [`Encoder::range_u32`], but for types that rand samples using
+    /// 64-bit words.
+    pub(crate) fn range_u64(&mut self, offset: u64, span: u64) {
+        debug_assert!(offset <= span);
+
+        match span.checked_add(1) {
+            Some(size) => {
+                let size = size as u128;
+                self.u64(((offset as u128) << 64).div_ceil(size) as u64)
+            }
+            None => self.u64(offset),
+        }
+    }
+
+    /// The inverse of `gen_length(rng, range)`.
+    pub(crate) fn length(&mut self, len: usize, range: &RangeInclusive<usize>) {
+        let (offset, span) = (len - range.start(), range.end() - range.start());
+        if *range.end() <= u8::MAX as usize || std::mem::size_of::<usize>() <= 4 {
+            self.range_u32(offset as u32, span as u32);
+        } else {
+            self.range_u64(offset as u64, span as u64);
+        }
+    }
+
+    /// The inverse of choosing the element at `index` from a slice or
+    /// exact-size iterator with `len` elements.
+    pub(crate) fn index(&mut self, index: usize, len: usize) {
+        self.range_u32(index as u32, len as u32 - 1);
+    }
+
+    /// Appends the words written to another encoder.
+    pub(crate) fn append(&mut self, other: Encoder) {
+        self.words.extend(other.words);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{parse_schema, recursive_schema};
+    use crate::{CharClass, FloatDistribution, NumberStrategy};
+    use chrono::{TimeZone, Utc};
+    use rand::SeedableRng;
+    use serde_json::json;
+
+    #[test]
+    fn test_encode_roundtrip() {
+        let schema = recursive_schema();
+
+        let configs = [
+            FuzzConfig::new(),
+            FuzzConfig::new()
+                .with_max_recursion_depth(1)
+                .with_null_probability(0.0)
+                .with_optional_property_probability(1.0),
+            FuzzConfig::new()
+                .with_string_length(3..=5)
+                .with_elements_length(1..=3)
+                .with_number_strategy(NumberStrategy::Edges)
+                .with_float_distribution(FloatDistribution::Bits)
+                .with_char_classes(vec![
+                    CharClass::PrintableAscii,
+                    CharClass::Bmp,
+                    CharClass::Astral,
+                    CharClass::Combining,
+                    CharClass::Rtl,
+                    CharClass::ZeroWidth,
+                    CharClass::Control,
+                    CharClass::Normalization,
+                ])
+                .with_timestamp_range(
+                    Utc.ymd(1, 1, 1).and_hms(0, 0, 0)..=Utc.ymd(2000, 1, 1).and_hms(0, 0, 0),
+                )
+                .with_timestamp_precision(9)
+                .with_timestamp_edges(true),
+            FuzzConfig::new().with_utc_timestamps(true),
+        ];
+
+        let mut rng = rand_pcg::Pcg32::seed_from_u64(8927);
+        for config in configs.iter().cycle().take(1000) {
+            let instance = fuzz_with_config(&schema, config, &mut rng);
+
+            // Most edge-case timestamps can't be encoded, so skip those.
+            let bytes = match encode_with_config(&schema, config, &instance) {
+                Err(err)
+                    if config.timestamp_edges
+                        && err.instance_path.last().map(String::as_str) == Some("value") =>
+                {
+                    continue
+                }
+                result => result.unwrap(),
+            };
+
+            assert_eq!(instance, decode(&schema, config, &bytes));
+        }
+    }
+
+    #[test]
+    fn test_encode_instances() {
+        let schema = parse_schema(json!({
+            "properties": {
+                "name": { "type": "string" },
+                "createdAt": { "type": "timestamp" },
+                "score": { "type": "float64" },
+                "favoriteNumbers": {
+                    "elements": { "type": "uint8" }
+                }
+            }
+        }));
+
+        // Numbers are compared by value, so whole numbers may be written
+        // without a fractional part.
+        let instance = json!({
+            "name": "Alice",
+            "createdAt": "2020-02-29T12:34:56+09:00",
+            "score": 0,
+            "favoriteNumbers": [1, 2, 3],
+        });
+
+        let bytes = encode(&schema, &instance).unwrap();
+        assert_eq!(
+            json!({
+                "name": "Alice",
+                "createdAt": "2020-02-29T12:34:56+09:00",
+                "score": 0.0,
+                "favoriteNumbers": [1, 2, 3],
+            }),
+            decode(&schema, &FuzzConfig::new(), &bytes)
+        );
+    }
+
+    #[test]
+    fn test_encode_errors() {
+        let schema = parse_schema(json!({
+            "properties": {
+                "name": { "type": "string" },
+                "createdAt": { "type": "timestamp" },
+                "score": { "type": "float64" },
+            },
+            "optionalProperties": {
+                "email": {
+                    "type": "string",
+                    "metadata": { "fuzzHint": "en_us/internet/email" }
+                },
+                "id": {
+                    "type": "string",
+                    "metadata": { "fuzzPattern": "^ORD-[0-9]{8}$" }
+                },
+            },
+        }));
+
+        let instance = json!({
+            "name": "Alice",
+            "createdAt": "2020-02-29T12:34:56+09:00",
+            "score": 0.5,
+        });
+
+        assert!(encode(&schema, &instance).is_ok());
+
+        let error = |key: &str, value: Value| {
+            let mut instance = instance.clone();
+            instance[key] = value;
+            encode(&schema, &instance).unwrap_err()
+        };
+
+        let expected = |key: &str, reason: &str| EncodeError {
+            instance_path: vec![key.into()],
+            reason: reason.into(),
+        };
+
+        assert_eq!(
+            expected("name", "instance is not valid against the schema"),
+            error("name", json!(1))
+        );
+
+        assert_eq!(
+            expected("name", "string length is outside of the configured range"),
+            error("name", json!("Bartholomew"))
+        );
+
+        assert_eq!(
+            expected(
+                "name",
+                "string has characters outside of the configured classes"
+            ),
+            error("name", json!("Zoë"))
+        );
+
+        assert_eq!(
+            expected("email", "strings with a fuzzHint cannot be encoded"),
+            error("email", json!("alice@example.com"))
+        );
+
+        assert_eq!(
+            expected("id", "strings with a fuzzPattern cannot be encoded"),
+            error("id", json!("ORD-12345678"))
+        );
+
+        assert_eq!(
+            expected(
+                "createdAt",
+                "timestamp is in UTC, but UTC timestamps are not enabled"
+            ),
+            error("createdAt", json!("2020-02-29T12:34:56Z"))
+        );
+
+        assert_eq!(
+            expected(
+                "createdAt",
+                "value would not be generated with this configuration"
+            ),
+            error("createdAt", json!("2020-02-29T12:34:56.5+09:00"))
+        );
+
+        assert_eq!(
+            expected("score", numbers::NOT_UNIT),
+            error("score", json!(0.1))
+        );
+
+        let schema = parse_schema(json!({
+            "definitions": {
+                "list": { "properties": { "next": { "ref": "list", "nullable": true }}},
+            },
+            "ref": "list",
+        }));
+
+        let mut instance = json!({ "next": null });
+        for _ in 0..5 {
+            instance = json!({ "next": instance });
+        }
+
+        assert_eq!(
+            EncodeError {
+                instance_path: vec!["next".into(); 5],
+                reason: "instance is nested too deeply for the configured max recursion depth"
+                    .into(),
+            },
+            encode(&schema, &instance).unwrap_err()
+        );
+    }
+
+    #[test]
+    fn test_encode_additional_properties() {
+        let schema = parse_schema(json!({
+            "properties": {
+                "k": { "type": "uint8" },
+            },
+            "optionalProperties": {
+                "s": { "type": "uint8" },
+            },
+            "additionalProperties": true,
+        }));
+        let config = FuzzConfig::new().with_char_classes(vec![CharClass::Bmp]);
+
+        let bytes = encode_with_config(&schema, &config, &json!({ "k": 1, "x": 2 })).unwrap();
+        assert_eq!(json!({ "k": 1, "x": 2 }), decode(&schema, &config, &bytes));
+
+        // Additional properties that Go would mistake for a defined property
+        // are never generated, and so can't be encoded.
+        for key in ["K", "\u{212a}", "S", "\u{17f}"] {
+            let mut instance = json!({ "k": 1 });
+            instance[key] = json!(2);
+
+            assert_eq!(
+                EncodeError {
+                    instance_path: vec![key.into()],
+                    reason: "additional properties never collide with a defined property".into(),
+                },
+                encode_with_config(&schema, &config, &instance).unwrap_err()
+            );
+        }
+    }
+
+    // Decodes bytes the same way fuzz_unstructured does.
+    fn decode(schema: &Schema, config: &FuzzConfig, bytes: &[u8]) -> Value {
+        let words = bytes
+            .chunks(4)
+            .map(|chunk| {
+                let mut word = [0; 4];
+                word[..chunk.len()].copy_from_slice(chunk);
+                u32::from_le_bytes(word)
+            })
+            .collect();
+
+        let config = config.clone().with_zero_words_smallest();
+        fuzz_with_config(schema, &config, &mut Replay { words, position: 0 })
+    }
+}
@@ -0,0 +1,471 @@
+use crate::hints::Generator;
+use rand::seq::SliceRandom;
+use rand::{Rng, RngCore};
+use std::fmt::Write;
+use std::net::{Ipv4Addr, Ipv6Addr};
+
+// Generators for strings in well-known, machine-readable formats, such as
+// UUIDs and IP addresses. Each of these generates only valid strings in its
+// format, and uses only the RNG it's given.
+
+// The hints for structured formats. When adding to this list, also add to the
+// list in the documentation of fuzz.
+pub(crate) const FORMAT_HINTS: &[(&str, Generator)] = &[
+    ("encoding/base64", base64),
+    ("encoding/base64url", base64url),
+    ("hash/md5", md5),
+    ("hash/sha1", sha1),
+    ("hash/sha256", sha256),
+    ("internet/cidr", cidr),
+    ("internet/hostname", hostname),
+    ("internet/ipv4", ipv4),
+    ("internet/ipv6", ipv6),
+    ("internet/mac_address", mac_address),
+    ("internet/mime_type", mime_type),
+    ("internet/uri", uri),
+    ("internet/url", url),
+    ("software/semver", semver),
+    ("uuid/v4", uuid_v4),
+    ("uuid/v7", uuid_v7),
+];
+
+const BASE64: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+const BASE64URL: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789-_";
+
+// Characters for hostname labels, URL paths, and the like. Hyphens are
+// handled separately, because labels may not start or end with one.
+const ALPHANUMERIC: &[u8] = b"abcdefghijklmnopqrstuvwxyz0123456789";
+
+const TOP_LEVEL_DOMAINS: &[&str] = &["com", "net", "org", "io", "dev", "example", "test"];
+
+const MIME_TYPES: &[&str] = &[
+    "application/json",
+    "application/ld+json",
+    "application/octet-stream",
+    "application/pdf",
+    "application/x-www-form-urlencoded",
+    "application/xml",
+    "application/zip",
+    "audio/mpeg",
+    "audio/ogg",
+    "font/woff2",
+    "image/gif",
+    "image/jpeg",
+    "image/png",
+    "image/svg+xml",
+    "image/webp",
+    "multipart/form-data",
+    "text/css",
+    "text/csv",
+    "text/html",
+    "text/javascript",
+    "text/plain",
+    "video/mp4",
+    "video/webm",
+];
+
+const PRE_RELEASES: &[&str] = &["alpha", "beta", "rc"];
+
+fn base64(rng: &mut dyn RngCore) -> String {
+    let bytes = random_bytes(rng, 0..=32);
+    encode_base64(BASE64, &bytes, true)
+}
+
+// base64url is most often used in URLs and tokens, such as JWTs, where it's
+// written without padding.
+fn base64url(rng: &mut dyn RngCore) -> String {
+    let bytes = random_bytes(rng, 0..=32);
+    encode_base64(BASE64URL, &bytes, false)
+}
+
+fn md5(rng: &mut dyn RngCore) -> String {
+    hex_digest(rng, 16)
+}
+
+fn sha1(rng: &mut dyn RngCore) -> String {
+    hex_digest(rng, 20)
+}
+
+fn sha256(rng: &mut dyn RngCore) -> String {
+    hex_digest(rng, 32)
+}
+
+// CIDR blocks are written with the bits past the prefix cleared, as networks
+// usually are.
+fn cidr(rng: &mut dyn RngCore) -> String {
+    if rng.gen() {
+        let prefix = rng.gen_range(0..=32);
+        let bits = u32::from(gen_ipv4(rng)) & !u32::MAX.checked_shr(prefix).unwrap_or(0);
+        format!("{}/{}", Ipv4Addr::from(bits), prefix)
+    } else {
+        let prefix = rng.gen_range(0..=128);
+        let bits = u128::from(gen_ipv6(rng)) & !u128::MAX.checked_shr(prefix).unwrap_or(0);
+        format!("{}/{}", Ipv6Addr::from(bits), prefix)
+    }
+}
+
+fn hostname(rng: &mut dyn RngCore) -> String {
+    let mut out = String::new();
+    for _ in 0..rng.gen_range(1..=3) {
+        out.push_str(&label(rng));
+        out.push('.');
+    }
+
+    out.push_str(TOP_LEVEL_DOMAINS.choose(rng).unwrap());
+    out
+}
+
+fn ipv4(rng: &mut dyn RngCore) -> String {
+    gen_ipv4(rng).to_string()
+}
+
+fn ipv6(rng: &mut dyn RngCore) -> String {
+    gen_ipv6(rng).to_string()
+}
+
+fn mac_address(rng: &mut dyn RngCore) -> String {
+    let mut bytes = [0; 6];
+    rng.fill_bytes(&mut bytes);
+
+    let octets: Vec<_> = bytes.iter().map(|b| format!("{:02x}", b)).collect();
+    octets.join(":")
+}
+
+fn mime_type(rng: &mut dyn RngCore) -> String {
+    MIME_TYPES.choose(rng).unwrap().to_string()
+}
+
+// A URI of any of several schemes, including the ones generated for URLs.
+fn uri(rng: &mut dyn RngCore) -> String {
+    match rng.gen_range(0..4) {
+        0 => format!("mailto:{}@{}", label(rng), hostname(rng)),
+        1 => format!("urn:uuid:{}", uuid_v4(rng)),
+        2 => format!(
+            "tel:+{}",
+            rng.gen_range(1_000_000_000u64..100_000_000_000_000)
+        ),
+        _ => url(rng),
+    }
+}
+
+fn url(rng: &mut dyn RngCore) -> String {
+    let mut out = format!(
+        "{}://{}",
+        if rng.gen() { "https" } else { "http" },
+        hostname(rng)
+    );
+
+    if rng.gen_ratio(1, 4) {
+        write!(out, ":{}", rng.gen_range(1..=u16::MAX)).unwrap();
+    }
+
+    for _ in 0..rng.gen_range(0..=3) {
+        write!(out, "/{}", label(rng)).unwrap();
+    }
+
+    if rng.gen_ratio(1, 4) {
+        for i in 0..rng.gen_range(1..=3) {
+            let sep = if i == 0 { '?' } else { '&' };
+            write!(out, "{}{}={}", sep, label(rng), label(rng)).unwrap();
+        }
+    }
+
+    if rng.gen_ratio(1, 4) {
+        write!(out, "#{}", label(rng)).unwrap();
+    }
+
+    out
+}
+
+fn semver(rng: &mut dyn RngCore) -> String {
+    let mut out = format!(
+        "{}.{}.{}",
+        rng.gen_range(0..=20),
+        rng.gen_range(0..=20),
+        rng.gen_range(0..=50)
+    );
+
+    if rng.gen_ratio(1, 4) {
+        let pre_release = PRE_RELEASES.choose(rng).unwrap();
+        write!(out, "-{}.{}", pre_release, rng.gen_range(0..=9)).unwrap();
+    }
+
+    if rng.gen_ratio(1, 8) {
+        write!(out, "+{}", hex_digest(rng, 4)).unwrap();
+    }
+
+    out
+}
+
+fn uuid_v4(rng: &mut dyn RngCore) -> String {
+    let mut bytes = [0; 16];
+    rng.fill_bytes(&mut bytes);
+    format_uuid(bytes, 4)
+}
+
+// UUIDv7s start with a Unix timestamp in milliseconds. The timestamps here are
+// from between 1970 and 2038, like the timestamps fuzz generates by default.
+fn uuid_v7(rng: &mut dyn RngCore) -> String {
+    let mut bytes = [0; 16];
+    rng.fill_bytes(&mut bytes);
+
+    let millis = rng.gen_range(0..=i32::MAX as u64 * 1000);
+    bytes[..6].copy_from_slice(&millis.to_be_bytes()[2..]);
+    format_uuid(bytes, 7)
+}
+
+fn format_uuid(mut bytes: [u8; 16], version: u8) -> String {
+    bytes[6] = (bytes[6] & 0x0f) | version << 4;
+    bytes[8] = (bytes[8] & 0x3f) | 0x80; // The RFC 4122 variant.
+
+    let hex = to_hex(&bytes);
+    format!(
+        "{}-{}-{}-{}-{}",
+        &hex[..8],
+        &hex[8..12],
+        &hex[12..16],
+        &hex[16..20],
+        &hex[20..]
+    )
+}
+
+fn gen_ipv4(rng: &mut dyn RngCore) -> Ipv4Addr {
+    Ipv4Addr::from(rng.next_u32())
+}
+
+// Groups of zeros are written in a shortened form in IPv6, so they're made
+// more common than they'd otherwise be, so that the shortened form turns up.
+fn gen_ipv6(rng: &mut dyn RngCore) -> Ipv6Addr {
+    let mut segments = [0u16; 8];
+    for segment in &mut segments {
+        if !rng.gen_ratio(1, 3) {
+            *segment = rng.gen();
+        }
+    }
+
+    Ipv6Addr::from(segments)
+}
+
+// A hostname label, per RFC 1123: letters, digits, and hyphens, not starting
+// or ending with a hyphen.
+fn label(rng: &mut dyn RngCore) -> String {
+    let len = rng.gen_range(1..=10);
+    (0..len)
+        .map(|i| {
+            if i != 0 && i != len - 1 && rng.gen_ratio(1, 10) {
+                '-'
+            } else {
+                *ALPHANUMERIC.choose(rng).unwrap() as char
+            }
+        })
+        .collect()
+}
+
+fn random_bytes(rng: &mut dyn RngCore, len: std::ops::RangeInclusive<usize>) -> Vec<u8> {
+    let mut bytes = vec![0; rng.gen_range(len)];
+    rng.fill_bytes(&mut bytes);
+    bytes
+}
+
+fn hex_digest(rng: &mut dyn RngCore, len: usize) -> String {
+    let mut bytes = vec![0; len];
+    rng.fill_bytes(&mut bytes);
+    to_hex(&bytes)
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn encode_base64(alphabet: &[u8; 64], bytes: &[u8], pad: bool) -> String {
+    let mut out = String::new();
+    for chunk in bytes.chunks(3) {
+        let mut group = [0; 3];
+        group[..chunk.len()].copy_from_slice(chunk);
+        let bits = u32::from_be_bytes([0, group[0], group[1], group[2]]);
+
+        // Each byte of input gives one more character of output, past the
+        // first.
+        for i in 0..=chunk.len() {
+            out.push(alphabet[(bits >> (18 - 6 * i) & 0x3f) as usize] as char);
+        }
+
+        if pad {
+            for _ in chunk.len()..3 {
+                out.push('=');
+            }
+        }
+    }
+
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::SeedableRng;
+    use std::str::FromStr;
+
+    #[test]
+    fn test_encode_base64() {
+        // The test vectors from RFC 4648.
+        let cases = [
+            ("", ""),
+            ("f", "Zg=="),
+            ("fo", "Zm8="),
+            ("foo", "Zm9v"),
+            ("foob", "Zm9vYg=="),
+            ("fooba", "Zm9vYmE="),
+            ("foobar", "Zm9vYmFy"),
+        ];
+
+        for (input, expected) in &cases {
+            assert_eq!(*expected, encode_base64(BASE64, input.as_bytes(), true));
+            assert_eq!(
+                expected.trim_end_matches('='),
+                encode_base64(BASE64URL, input.as_bytes(), false)
+            );
+        }
+
+        assert_eq!("+/+/", encode_base64(BASE64, &[0xfb, 0xff, 0xbf], true));
+        assert_eq!("-_-_", encode_base64(BASE64URL, &[0xfb, 0xff, 0xbf], false));
+    }
+
+    #[test]
+    fn test_format_hints() {
+        let mut rng = rand_pcg::Pcg32::seed_from_u64(8927);
+        for _ in 0..1000 {
+            let base64 = base64(&mut rng);
+            assert_eq!(0, base64.len() % 4);
+            assert!(base64
+                .trim_end_matches('=')
+                .bytes()
+                .all(|b| BASE64.contains(&b)));
+
+            let base64url = base64url(&mut rng);
+            assert!(base64url.len() % 4 != 1);
+            assert!(base64url.bytes().all(|b| BASE64URL.contains(&b)));
+
+            assert_hex(&md5(&mut rng), 32);
+            assert_hex(&sha1(&mut rng), 40);
+            assert_hex(&sha256(&mut rng), 64);
+
+            let cidr = cidr(&mut rng);
+            let (addr, prefix) = cidr.split_once('/').unwrap();
+            let prefix: u32 = prefix.parse().unwrap();
+            if let Ok(addr) = Ipv4Addr::from_str(addr) {
+                assert!(prefix <= 32);
+                assert_eq!(0, u32::from(addr).checked_shl(prefix).unwrap_or(0));
+            } else {
+                let addr = Ipv6Addr::from_str(addr).unwrap();
+                assert!(prefix <= 128);
+                assert_eq!(0, u128::from(addr).checked_shl(prefix).unwrap_or(0));
+            }
+
+            assert_hostname(&hostname(&mut rng));
+
+            let ipv4 = ipv4(&mut rng);
+            assert_eq!(ipv4, Ipv4Addr::from_str(&ipv4).unwrap().to_string());
+
+            let ipv6 = ipv6(&mut rng);
+            assert_eq!(ipv6, Ipv6Addr::from_str(&ipv6).unwrap().to_string());
+
+            let mac_address = mac_address(&mut rng);
+            assert_eq!(6, mac_address.split(':').count());
+            for octet in mac_address.split(':') {
+                assert_hex(octet, 2);
+            }
+
+            assert!(MIME_TYPES.contains(&mime_type(&mut rng).as_str()));
+
+            let uri = uri(&mut rng);
+            let (scheme, rest) = uri.split_once(':').unwrap();
+            assert!(["mailto", "urn", "tel", "http", "https"].contains(&scheme));
+            assert!(!rest.is_empty());
+
+            assert_url(&url(&mut rng));
+            assert_semver(&semver(&mut rng));
+            assert_uuid(&uuid_v4(&mut rng), '4');
+            assert_uuid(&uuid_v7(&mut rng), '7');
+        }
+    }
+
+    fn assert_hex(s: &str, len: usize) {
+        assert_eq!(len, s.len(), "{}", s);
+        assert!(s.bytes().all(|b| b"0123456789abcdef".contains(&b)), "{}", s);
+    }
+
+    fn assert_hostname(s: &str) {
+        let labels: Vec<_> = s.split('.').collect();
+        assert!(labels.len() >= 2, "{}", s);
+        for label in labels {
+            assert!(!label.is_empty() && label.len() <= 63, "{}", s);
+            assert!(!label.starts_with('-') && !label.ends_with('-'), "{}", s);
+            assert!(
+                label
+                    .bytes()
+                    .all(|b| b == b'-' || ALPHANUMERIC.contains(&b)),
+                "{}",
+                s
+            );
+        }
+    }
+
+    fn assert_url(s: &str) {
+        let rest = s
+            .strip_prefix("https://")
+            .or_else(|| s.strip_prefix("http://"))
+            .unwrap();
+        let authority = rest.split(&['/', '?', '#'][..]).next().unwrap();
+        let host = match authority.split_once(':') {
+            Some((host, port)) => {
+                assert!(port.parse::<u16>().unwrap() > 0, "{}", s);
+                host
+            }
+            None => authority,
+        };
+
+        assert_hostname(host);
+    }
+
+    fn assert_semver(s: &str) {
+        let version = match s.split_once('+') {
+            Some((version, build)) => {
+                assert_hex(build, 8);
+                version
+            }
+            None => s,
+        };
+
+        let version = match version.split_once('-') {
+            Some((version, pre_release)) => {
+                let (name, n) = pre_release.split_once('.').unwrap();
+                assert!(PRE_RELEASES.contains(&name), "{}", s);
+                n.parse::<u8>().unwrap();
+                version
+            }
+            None => version,
+        };
+
+        let parts: Vec<_> = version.split('.').collect();
+        assert_eq!(3, parts.len(), "{}", s);
+        for part in parts {
+            let n: u64 = part.parse().unwrap();
+            assert_eq!(n.to_string(), part, "{}", s);
+        }
+    }
+
+    fn assert_uuid(s: &str, version: char) {
+        let groups: Vec<_> = s.split('-').collect();
+        assert_eq!(
+            vec![8, 4, 4, 4, 12],
+            groups.iter().map(|g| g.len()).collect::<Vec<_>>()
+        );
+        for group in &groups {
+            assert_hex(group, group.len());
+        }
+
+        assert_eq!(Some(version), groups[2].chars().next(), "{}", s);
+        assert!("89ab".contains(groups[3].chars().next().unwrap()), "{}", s);
+    }
+}
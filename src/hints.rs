@@ -0,0 +1,371 @@
+use crate::dates::{Strftime, DATE_HINTS};
+use crate::formats::FORMAT_HINTS;
+use rand::distributions::{Distribution, Standard};
+use rand::{Rng, RngCore};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::sync::{Arc, OnceLock};
+
+/// Generates strings for a `fuzzHint`.
+///
+/// This is implemented for any function or closure that takes an RNG and
+/// returns a `String`, so those can be registered as hints directly. Like the
+/// rest of this crate, a generator should use only the RNG it's given as its
+/// source of randomness, so that the same seed always gives the same output.
+///
+/// Generators must be [`Send`] and [`Sync`], so that a [`FuzzConfig`] can be
+/// shared between threads that generate values in parallel.
+///
+/// [`FuzzConfig`]: crate::FuzzConfig
+pub trait HintGenerator: Send + Sync {
+    /// Generates a string for the hint.
+    fn generate(&self, rng: &mut dyn RngCore) -> String;
+}
+
+impl<F> HintGenerator for F
+where
+    F: Fn(&mut dyn RngCore) -> String + Send + Sync,
+{
+    fn generate(&self, rng: &mut dyn RngCore) -> String {
+        self(rng)
+    }
+}
+
+/// The values of `fuzzHint` that are recognized, and how to generate strings
+/// for each of them.
+///
+/// [`HintRegistry::new`] has all the hints listed in [`fuzz`](crate::fuzz),
+/// which are what [`FuzzConfig`](crate::FuzzConfig) uses by default. To add
+/// your own hints, [`register`](Self::register) them, and then pass the
+/// registry to [`FuzzConfig::with_hints`](crate::FuzzConfig::with_hints):
+///
+/// ```
+/// use serde_json::json;
+/// use rand::{Rng, SeedableRng};
+///
+/// let mut hints = jtd_fuzz::HintRegistry::new();
+/// hints.register("acme/order_id", |rng: &mut dyn rand::RngCore| {
+///     format!("ORD-{:08}", rng.gen_range(0..100_000_000))
+/// });
+///
+/// let config = jtd_fuzz::FuzzConfig::new().with_hints(hints);
+///
+/// let schema = jtd::Schema::from_serde_schema(serde_json::from_value(json!({
+///     "type": "string",
+///     "metadata": { "fuzzHint": "acme/order_id" }
+/// })).unwrap()).unwrap();
+///
+/// let mut rng = rand_pcg::Pcg32::seed_from_u64(8927);
+/// let order_id = jtd_fuzz::fuzz_with_config(&schema, &config, &mut rng);
+/// assert!(order_id.as_str().unwrap().starts_with("ORD-"));
+/// ```
+///
+/// Registries are compared by the hints they have, and by which generators
+/// those hints use, rather than by what those generators do.
+#[derive(Clone)]
+pub struct HintRegistry {
+    hints: BTreeMap<String, Arc<dyn HintGenerator>>,
+}
+
+impl HintRegistry {
+    /// Constructs a registry of all the hints built into this crate.
+    ///
+    /// Equivalent to [`Default::default()`].
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Constructs a registry with no hints at all.
+    ///
+    /// With such a registry, every `fuzzHint` is ignored, other than
+    /// `strftime:` patterns, which every registry recognizes.
+    pub fn empty() -> Self {
+        Self {
+            hints: BTreeMap::new(),
+        }
+    }
+
+    /// Adds a hint, replacing any existing hint with the same name.
+    pub fn register<G: HintGenerator + 'static>(&mut self, name: impl Into<String>, generator: G) {
+        self.hints.insert(name.into(), Arc::new(generator));
+    }
+
+    /// Returns the generator for a registered hint, if there is one.
+    ///
+    /// This doesn't return generators for `strftime:` patterns, which aren't
+    /// registered.
+    pub fn get(&self, name: &str) -> Option<&dyn HintGenerator> {
+        self.hints.get(name).map(|generator| &**generator)
+    }
+
+    /// Returns the names of all the hints, in sorted order.
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.hints.keys().map(String::as_str)
+    }
+
+    // Returns a shared handle to the generator for a hint, if there is one.
+    // Registered hints take precedence over strftime patterns.
+    pub(crate) fn resolve(&self, name: &str) -> Option<SharedHint> {
+        match self.hints.get(name) {
+            Some(generator) => Some(SharedHint(generator.clone())),
+            None => Strftime::parse(name).map(SharedHint::new),
+        }
+    }
+}
+
+impl Default for HintRegistry {
+    fn default() -> Self {
+        // Every default registry shares the same generators, so that default
+        // registries are equal to one another.
+        static BUILT_IN: OnceLock<HintRegistry> = OnceLock::new();
+
+        BUILT_IN
+            .get_or_init(|| {
+                let mut hints = HintRegistry::empty();
+                for (name, generator) in FAKER_HINTS.iter().chain(FORMAT_HINTS).chain(DATE_HINTS) {
+                    hints.register(*name, *generator);
+                }
+
+                hints
+            })
+            .clone()
+    }
+}
+
+impl PartialEq for HintRegistry {
+    fn eq(&self, other: &Self) -> bool {
+        self.hints.len() == other.hints.len()
+            && self
+                .hints
+                .iter()
+                .zip(&other.hints)
+                .all(|((a, f), (b, g))| a == b && Arc::ptr_eq(f, g))
+    }
+}
+
+impl fmt::Debug for HintRegistry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_set().entries(self.names()).finish()
+    }
+}
+
+// A generator from a registry, kept around after the fact.
+#[derive(Clone)]
+pub(crate) struct SharedHint(Arc<dyn HintGenerator>);
+
+impl SharedHint {
+    pub(crate) fn new<G: HintGenerator + 'static>(generator: G) -> Self {
+        Self(Arc::new(generator))
+    }
+
+    pub(crate) fn get(&self) -> &dyn HintGenerator {
+        &*self.0
+    }
+}
+
+impl fmt::Debug for SharedHint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("SharedHint")
+    }
+}
+
+// A built-in hint's generator.
+pub(crate) type Generator = fn(&mut dyn RngCore) -> String;
+
+// Generates a fake value with faker_rand.
+fn faker<T: ToString>(rng: &mut dyn RngCore) -> String
+where
+    Standard: Distribution<T>,
+{
+    rng.gen::<T>().to_string()
+}
+
+// The hints built on faker_rand. When adding to this list, also add to the list
+// in the documentation of fuzz.
+const FAKER_HINTS: &[(&str, Generator)] = &[
+    (
+        "en_us/addresses/address",
+        faker::<faker_rand::en_us::addresses::Address>,
+    ),
+    (
+        "en_us/addresses/city_name",
+        faker::<faker_rand::en_us::addresses::CityName>,
+    ),
+    (
+        "en_us/addresses/division",
+        faker::<faker_rand::en_us::addresses::Division>,
+    ),
+    (
+        "en_us/addresses/division_abbreviation",
+        faker::<faker_rand::en_us::addresses::DivisionAbbreviation>,
+    ),
+    (
+        "en_us/addresses/postal_code",
+        faker::<faker_rand::en_us::addresses::PostalCode>,
+    ),
+    (
+        "en_us/addresses/secondary_address",
+        faker::<faker_rand::en_us::addresses::SecondaryAddress>,
+    ),
+    (
+        "en_us/addresses/street_address",
+        faker::<faker_rand::en_us::addresses::StreetAddress>,
+    ),
+    (
+        "en_us/addresses/street_name",
+        faker::<faker_rand::en_us::addresses::StreetName>,
+    ),
+    (
+        "en_us/company/company_name",
+        faker::<faker_rand::en_us::company::CompanyName>,
+    ),
+    (
+        "en_us/company/slogan",
+        faker::<faker_rand::en_us::company::Slogan>,
+    ),
+    (
+        "en_us/internet/domain",
+        faker::<faker_rand::en_us::internet::Domain>,
+    ),
+    (
+        "en_us/internet/email",
+        faker::<faker_rand::en_us::internet::Email>,
+    ),
+    (
+        "en_us/internet/username",
+        faker::<faker_rand::en_us::internet::Username>,
+    ),
+    (
+        "en_us/names/first_name",
+        faker::<faker_rand::en_us::names::FirstName>,
+    ),
+    (
+        "en_us/names/full_name",
+        faker::<faker_rand::en_us::names::FullName>,
+    ),
+    (
+        "en_us/names/last_name",
+        faker::<faker_rand::en_us::names::LastName>,
+    ),
+    (
+        "en_us/names/name_prefix",
+        faker::<faker_rand::en_us::names::NamePrefix>,
+    ),
+    (
+        "en_us/names/name_suffix",
+        faker::<faker_rand::en_us::names::NameSuffix>,
+    ),
+    (
+        "en_us/phones/phone_number",
+        faker::<faker_rand::en_us::phones::PhoneNumber>,
+    ),
+    (
+        "fr_fr/addresses/address",
+        faker::<faker_rand::fr_fr::addresses::Address>,
+    ),
+    (
+        "fr_fr/addresses/city_name",
+        faker::<faker_rand::fr_fr::addresses::CityName>,
+    ),
+    (
+        "fr_fr/addresses/division",
+        faker::<faker_rand::fr_fr::addresses::Division>,
+    ),
+    (
+        "fr_fr/addresses/postal_code",
+        faker::<faker_rand::fr_fr::addresses::PostalCode>,
+    ),
+    (
+        "fr_fr/addresses/secondary_address",
+        faker::<faker_rand::fr_fr::addresses::SecondaryAddress>,
+    ),
+    (
+        "fr_fr/addresses/street_address",
+        faker::<faker_rand::fr_fr::addresses::StreetAddress>,
+    ),
+    (
+        "fr_fr/addresses/street_name",
+        faker::<faker_rand::fr_fr::addresses::StreetName>,
+    ),
+    (
+        "fr_fr/company/company_name",
+        faker::<faker_rand::fr_fr::company::CompanyName>,
+    ),
+    (
+        "fr_fr/internet/domain",
+        faker::<faker_rand::fr_fr::internet::Domain>,
+    ),
+    (
+        "fr_fr/internet/email",
+        faker::<faker_rand::fr_fr::internet::Email>,
+    ),
+    (
+        "fr_fr/internet/username",
+        faker::<faker_rand::fr_fr::internet::Username>,
+    ),
+    (
+        "fr_fr/names/first_name",
+        faker::<faker_rand::fr_fr::names::FirstName>,
+    ),
+    (
+        "fr_fr/names/full_name",
+        faker::<faker_rand::fr_fr::names::FullName>,
+    ),
+    (
+        "fr_fr/names/last_name",
+        faker::<faker_rand::fr_fr::names::LastName>,
+    ),
+    (
+        "fr_fr/names/name_prefix",
+        faker::<faker_rand::fr_fr::names::NamePrefix>,
+    ),
+    (
+        "fr_fr/phones/phone_number",
+        faker::<faker_rand::fr_fr::phones::PhoneNumber>,
+    ),
+    ("lorem/word", faker::<faker_rand::lorem::Word>),
+    ("lorem/sentence", faker::<faker_rand::lorem::Sentence>),
+    ("lorem/paragraph", faker::<faker_rand::lorem::Paragraph>),
+    ("lorem/paragraphs", faker::<faker_rand::lorem::Paragraphs>),
+];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::SeedableRng;
+
+    #[test]
+    fn test_hint_registry() {
+        assert_eq!(HintRegistry::new(), HintRegistry::default());
+        assert_ne!(HintRegistry::new(), HintRegistry::empty());
+        assert_eq!(
+            FAKER_HINTS.len() + FORMAT_HINTS.len() + DATE_HINTS.len(),
+            HintRegistry::new().names().count()
+        );
+
+        let mut hints = HintRegistry::new();
+        hints.register("acme/order_id", |rng: &mut dyn RngCore| {
+            format!("ORD-{:08}", rng.gen_range(0..100_000_000))
+        });
+        hints.register("lorem/word", |_: &mut dyn RngCore| "lorem".to_owned());
+
+        assert_ne!(HintRegistry::new(), hints);
+        assert_eq!(hints.clone(), hints);
+        assert_eq!(
+            FAKER_HINTS.len() + FORMAT_HINTS.len() + DATE_HINTS.len() + 1,
+            hints.names().count()
+        );
+
+        let mut rng = rand_pcg::Pcg32::seed_from_u64(8927);
+        let order_id = hints.get("acme/order_id").unwrap().generate(&mut rng);
+        assert!(order_id.starts_with("ORD-") && order_id.len() == 12);
+        assert_eq!("lorem", hints.get("lorem/word").unwrap().generate(&mut rng));
+        assert!(hints.get("acme/unknown").is_none());
+
+        assert!(hints.get("strftime:%Y").is_none());
+        assert!(hints.resolve("strftime:%Y").is_some());
+        assert!(HintRegistry::empty().resolve("strftime:%Y").is_some());
+        assert!(hints.resolve("strftime:%Q").is_none());
+        assert!(hints.resolve("acme/unknown").is_none());
+    }
+}
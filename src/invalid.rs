@@ -0,0 +1,602 @@
+use crate::strings::fuzz_string;
+use crate::{fuzz_with_config, FuzzConfig};
+use jtd::{Schema, Type};
+use rand::seq::{IteratorRandom, SliceRandom};
+use serde_json::Value;
+use std::collections::BTreeSet;
+
+/// An instance that fails validation against a schema in exactly one way.
+///
+/// Returned by [`fuzz_invalid`].
+#[derive(Clone, Debug, PartialEq)]
+pub struct InvalidInstance {
+    /// The invalid instance.
+    pub instance: Value,
+
+    /// A path to the part of the instance that [`jtd::validate`] will reject.
+    pub instance_path: Vec<String>,
+
+    /// A path to the part of the schema that [`jtd::validate`] will report as
+    /// rejecting the instance.
+    pub schema_path: Vec<String>,
+}
+
+/// Generates a random JSON value that violates a given schema in exactly one
+/// way.
+///
+/// The instance is generated by taking a valid instance, as from [`fuzz`], and
+/// then making one change to it. That change is one of:
+///
+/// * Replacing a value with one of the wrong type, such as a string where the
+///   schema expects a boolean, or `null` where the schema isn't nullable.
+/// * Replacing an integer with one outside of the range of its type, such as
+///   `256` for `uint8`, or with a number that isn't an integer.
+/// * Replacing a timestamp with a string that isn't a valid RFC3339
+///   timestamp.
+/// * Replacing a value of an `enum` with a string not in the enum.
+/// * Removing a required property.
+/// * Adding a property a schema doesn't allow, when `additionalProperties` is
+///   not `true`.
+/// * Replacing a discriminator's tag with a value not in its `mapping`.
+///
+/// The returned [`InvalidInstance`] also contains the one validation error
+/// that [`jtd::validate`] will return for the instance.
+///
+/// Some schemas, such as the empty schema `{}`, accept every possible JSON
+/// value. In that case, there is nothing to generate, and this function returns
+/// `None`.
+///
+/// ```
+/// use serde_json::json;
+/// use rand::SeedableRng;
+///
+/// let schema = jtd::Schema::from_serde_schema(serde_json::from_value(json!({
+///     "properties": {
+///         "name": { "type": "string" },
+///         "age": { "type": "uint8" }
+///     }
+/// })).unwrap()).unwrap();
+///
+/// let mut rng = rand_pcg::Pcg32::seed_from_u64(8927);
+/// let invalid = jtd_fuzz::fuzz_invalid(&schema, &mut rng).unwrap();
+///
+/// let errors = jtd::validate(&schema, &invalid.instance, Default::default()).unwrap();
+/// let errors: Vec<_> = errors.into_iter().map(|e| e.into_owned_paths()).collect();
+/// assert_eq!(errors, vec![(invalid.instance_path, invalid.schema_path)]);
+/// ```
+///
+/// [`fuzz`]: crate::fuzz
+pub fn fuzz_invalid<R: rand::Rng>(schema: &Schema, rng: &mut R) -> Option<InvalidInstance> {
+    fuzz_invalid_with_config(schema, &FuzzConfig::default(), rng)
+}
+
+/// Generates a random JSON value that violates a given schema in exactly one
+/// way, using the given configuration.
+///
+/// This is like [`fuzz_invalid`], except that the valid instance it starts
+/// from is generated as with [`fuzz_with_config`].
+pub fn fuzz_invalid_with_config<R: rand::Rng>(
+    schema: &Schema,
+    config: &FuzzConfig,
+    rng: &mut R,
+) -> Option<InvalidInstance> {
+    let mut instance = fuzz_with_config(schema, config, rng);
+
+    let mut collector = Collector {
+        root: schema,
+        instance_path: vec![],
+        schema_path: vec![vec![]],
+        sites: vec![],
+    };
+
+    collector.collect(schema, None, false, &instance);
+    if collector.sites.is_empty() {
+        return None;
+    }
+
+    let site = collector
+        .sites
+        .swap_remove(rng.gen_range(0..collector.sites.len()));
+
+    let target = site
+        .instance_path
+        .iter()
+        .try_fold(&mut instance, |value, token| match value {
+            Value::Array(arr) => arr.get_mut(token.parse::<usize>().ok()?),
+            Value::Object(obj) => obj.get_mut(token),
+            _ => None,
+        })
+        .unwrap();
+
+    let mut instance_path = site.instance_path;
+    let mut schema_path = site.schema_path;
+
+    match site.mutation {
+        Mutation::WrongType { schema, nullable } => {
+            *target = wrong_type(config, rng, schema, nullable);
+        }
+
+        Mutation::OutOfRange(type_) => {
+            let (min, max) = match type_ {
+                Type::Int8 => (i8::MIN as i64, i8::MAX as i64),
+                Type::Uint8 => (u8::MIN as i64, u8::MAX as i64),
+                Type::Int16 => (i16::MIN as i64, i16::MAX as i64),
+                Type::Uint16 => (u16::MIN as i64, u16::MAX as i64),
+                Type::Int32 => (i32::MIN as i64, i32::MAX as i64),
+                Type::Uint32 => (u32::MIN as i64, u32::MAX as i64),
+                _ => unreachable!("out of range mutation on non-integer type"),
+            };
+
+            *target = match rng.gen_range(0..3) {
+                0 => (min - 1).into(),
+                1 => (max + 1).into(),
+
+                // A number in range, but which isn't an integer.
+                _ => (rng.gen_range(min..max) as f64 + 0.5).into(),
+            };
+        }
+
+        Mutation::BadTimestamp => {
+            *target = BAD_TIMESTAMPS.choose(rng).unwrap().to_string().into();
+        }
+
+        Mutation::NotInEnum(enum_) => {
+            let member = enum_.iter().choose(rng).unwrap();
+            *target = unused_string(rng, member, |s| enum_.contains(s)).into();
+        }
+
+        Mutation::MissingProperty(name) => {
+            target.as_object_mut().unwrap().remove(name);
+        }
+
+        Mutation::UnexpectedProperty {
+            properties,
+            optional_properties,
+            parent_tag,
+        } => {
+            let base = fuzz_string(config, rng);
+            let name = unused_string(rng, &base, |s| {
+                properties.contains(s) || optional_properties.contains(s) || parent_tag == Some(s)
+            });
+
+            let value = fuzz_with_config(
+                &Schema::Empty {
+                    metadata: Default::default(),
+                    definitions: Default::default(),
+                },
+                config,
+                rng,
+            );
+
+            target.as_object_mut().unwrap().insert(name.clone(), value);
+            instance_path.push(name);
+        }
+
+        Mutation::UnknownTag {
+            discriminator,
+            mapping,
+        } => {
+            let tag = mapping.iter().choose(rng).unwrap();
+            target.as_object_mut().unwrap().insert(
+                discriminator.to_owned(),
+                unused_string(rng, tag, |s| mapping.contains(s)).into(),
+            );
+
+            instance_path.push(discriminator.to_owned());
+            schema_path.push("mapping".to_owned());
+        }
+    }
+
+    Some(InvalidInstance {
+        instance,
+        instance_path,
+        schema_path,
+    })
+}
+
+// Strings that look like, but are not, RFC3339 timestamps.
+const BAD_TIMESTAMPS: &[&str] = &[
+    "",
+    "not a timestamp",
+    "2020-01-01",
+    "12:00:00Z",
+    "2020-01-01T12:00:00",
+    "2020-13-01T12:00:00Z",
+    "2021-02-29T12:00:00Z",
+    "2020-01-01T24:00:01Z",
+    "2020-01-01T12:00:00+25:00",
+    "2020-01-01T12:00:00 UTC",
+    "1577880000",
+];
+
+// A place in an instance where a mutation can be made, as well as the path to
+// the part of the schema that will reject that mutation.
+struct Site<'a> {
+    instance_path: Vec<String>,
+    schema_path: Vec<String>,
+    mutation: Mutation<'a>,
+}
+
+enum Mutation<'a> {
+    WrongType {
+        schema: &'a Schema,
+        nullable: bool,
+    },
+    OutOfRange(&'a Type),
+    BadTimestamp,
+    NotInEnum(&'a BTreeSet<String>),
+    MissingProperty(&'a str),
+    UnexpectedProperty {
+        properties: BTreeSet<&'a str>,
+        optional_properties: BTreeSet<&'a str>,
+        parent_tag: Option<&'a str>,
+    },
+    UnknownTag {
+        discriminator: &'a str,
+        mapping: BTreeSet<&'a str>,
+    },
+}
+
+// Walks a valid instance alongside its schema, keeping track of paths the same
+// way jtd::validate does, and collects every site where a mutation can be made.
+struct Collector<'a> {
+    root: &'a Schema,
+    instance_path: Vec<String>,
+
+    // Following a ref resets the schema path to the definition, so we keep a
+    // stack of schema paths, one per ref followed.
+    schema_path: Vec<Vec<String>>,
+
+    sites: Vec<Site<'a>>,
+}
+
+impl<'a> Collector<'a> {
+    fn collect(
+        &mut self,
+        schema: &'a Schema,
+        parent_tag: Option<&'a str>,
+        nullable: bool,
+        instance: &Value,
+    ) {
+        let nullable = nullable || schema.nullable();
+
+        match schema {
+            Schema::Empty { .. } => {}
+
+            Schema::Ref { ref ref_, .. } => {
+                self.schema_path
+                    .push(vec!["definitions".to_owned(), ref_.clone()]);
+                self.collect(&self.root.definitions()[ref_], None, nullable, instance);
+                self.schema_path.pop();
+            }
+
+            Schema::Type { ref type_, .. } => {
+                self.push_schema_token("type");
+                self.push_site(Mutation::WrongType { schema, nullable });
+
+                if !instance.is_null() {
+                    match type_ {
+                        Type::Int8
+                        | Type::Uint8
+                        | Type::Int16
+                        | Type::Uint16
+                        | Type::Int32
+                        | Type::Uint32 => self.push_site(Mutation::OutOfRange(type_)),
+                        Type::Timestamp => self.push_site(Mutation::BadTimestamp),
+                        _ => {}
+                    }
+                }
+
+                self.pop_schema_token();
+            }
+
+            Schema::Enum { ref enum_, .. } => {
+                self.push_schema_token("enum");
+                self.push_site(Mutation::WrongType { schema, nullable });
+
+                if !instance.is_null() {
+                    self.push_site(Mutation::NotInEnum(enum_));
+                }
+
+                self.pop_schema_token();
+            }
+
+            Schema::Elements { ref elements, .. } => {
+                self.push_schema_token("elements");
+                self.push_site(Mutation::WrongType { schema, nullable });
+
+                for (i, sub_instance) in instance.as_array().into_iter().flatten().enumerate() {
+                    self.instance_path.push(i.to_string());
+                    self.collect(elements, None, false, sub_instance);
+                    self.instance_path.pop();
+                }
+
+                self.pop_schema_token();
+            }
+
+            Schema::Properties {
+                ref properties,
+                ref optional_properties,
+                properties_is_present,
+                additional_properties,
+                ..
+            } => {
+                // A discriminator's variants are validated against the same
+                // object as the discriminator itself, so the wrong type is
+                // reported against the discriminator instead.
+                if parent_tag.is_none() {
+                    self.push_schema_token(if *properties_is_present {
+                        "properties"
+                    } else {
+                        "optionalProperties"
+                    });
+                    self.push_site(Mutation::WrongType { schema, nullable });
+                    self.pop_schema_token();
+                }
+
+                let obj = match instance.as_object() {
+                    Some(obj) => obj,
+                    None => return,
+                };
+
+                self.push_schema_token("properties");
+                for (name, sub_schema) in properties {
+                    self.push_schema_token(name);
+                    self.push_site(Mutation::MissingProperty(name));
+
+                    self.instance_path.push(name.clone());
+                    self.collect(sub_schema, None, false, &obj[name]);
+                    self.instance_path.pop();
+
+                    self.pop_schema_token();
+                }
+                self.pop_schema_token();
+
+                self.push_schema_token("optionalProperties");
+                for (name, sub_schema) in optional_properties {
+                    if let Some(sub_instance) = obj.get(name) {
+                        self.push_schema_token(name);
+                        self.instance_path.push(name.clone());
+                        self.collect(sub_schema, None, false, sub_instance);
+                        self.instance_path.pop();
+                        self.pop_schema_token();
+                    }
+                }
+                self.pop_schema_token();
+
+                if !*additional_properties {
+                    self.push_site(Mutation::UnexpectedProperty {
+                        properties: properties.keys().map(String::as_str).collect(),
+                        optional_properties: optional_properties
+                            .keys()
+                            .map(String::as_str)
+                            .collect(),
+                        parent_tag,
+                    });
+                }
+            }
+
+            Schema::Values { ref values, .. } => {
+                self.push_schema_token("values");
+                self.push_site(Mutation::WrongType { schema, nullable });
+
+                for (name, sub_instance) in instance.as_object().into_iter().flatten() {
+                    self.instance_path.push(name.clone());
+                    self.collect(values, None, false, sub_instance);
+                    self.instance_path.pop();
+                }
+
+                self.pop_schema_token();
+            }
+
+            Schema::Discriminator {
+                ref discriminator,
+                ref mapping,
+                ..
+            } => {
+                self.push_schema_token("discriminator");
+                self.push_site(Mutation::WrongType { schema, nullable });
+                self.pop_schema_token();
+
+                let tag = match instance.get(discriminator).and_then(Value::as_str) {
+                    Some(tag) => tag,
+                    None => return,
+                };
+
+                self.push_site(Mutation::UnknownTag {
+                    discriminator,
+                    mapping: mapping.keys().map(String::as_str).collect(),
+                });
+
+                self.push_schema_token("mapping");
+                self.push_schema_token(tag);
+                self.collect(&mapping[tag], Some(discriminator), false, instance);
+                self.pop_schema_token();
+                self.pop_schema_token();
+            }
+        }
+    }
+
+    fn push_site(&mut self, mutation: Mutation<'a>) {
+        self.sites.push(Site {
+            instance_path: self.instance_path.clone(),
+            schema_path: self.schema_path.last().unwrap().clone(),
+            mutation,
+        });
+    }
+
+    fn push_schema_token(&mut self, token: &str) {
+        self.schema_path.last_mut().unwrap().push(token.to_owned());
+    }
+
+    fn pop_schema_token(&mut self) {
+        self.schema_path.last_mut().unwrap().pop().unwrap();
+    }
+}
+
+// Generates a value of a JSON type that schema does not accept.
+fn wrong_type<R: rand::Rng>(
+    config: &FuzzConfig,
+    rng: &mut R,
+    schema: &Schema,
+    nullable: bool,
+) -> Value {
+    // Each of these is: null, boolean, number, string, array, object.
+    let mut accepted = match schema {
+        Schema::Type { ref type_, .. } => match type_ {
+            Type::Boolean => [false, true, false, false, false, false],
+            Type::String | Type::Timestamp => [false, false, false, true, false, false],
+
+            // Numbers of the right type but the wrong value are handled
+            // separately, by Mutation::OutOfRange.
+            _ => [false, false, true, false, false, false],
+        },
+        Schema::Enum { .. } => [false, false, false, true, false, false],
+        Schema::Elements { .. } => [false, false, false, false, true, false],
+        Schema::Properties { .. } | Schema::Values { .. } | Schema::Discriminator { .. } => {
+            [false, false, false, false, false, true]
+        }
+        Schema::Empty { .. } | Schema::Ref { .. } => {
+            unreachable!("wrong type mutation on schema that accepts any type")
+        }
+    };
+
+    accepted[0] = nullable;
+
+    let kind = (0..6).filter(|&i| !accepted[i]).choose(rng).unwrap();
+
+    match kind {
+        0 => Value::Null,
+        1 => rng.gen::<bool>().into(),
+        2 => rng.gen::<u8>().into(),
+        3 => fuzz_string(config, rng).into(),
+        4 => Value::Array(vec![]),
+        _ => Value::Object(Default::default()),
+    }
+}
+
+// Generates a string, starting from base, for which is_used returns false.
+fn unused_string<R: rand::Rng>(rng: &mut R, base: &str, is_used: impl Fn(&str) -> bool) -> String {
+    let mut s = base.to_owned();
+    while is_used(&s) {
+        s.push(rng.gen_range(b'a'..=b'z') as char);
+    }
+
+    s
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_fuzz_invalid_empty() {
+        use rand::SeedableRng;
+
+        let schema = Schema::from_serde_schema(serde_json::from_value(json!({})).unwrap()).unwrap();
+        let mut rng = rand_pcg::Pcg32::seed_from_u64(8927);
+        assert_eq!(None, fuzz_invalid(&schema, &mut rng));
+    }
+
+    #[test]
+    fn test_fuzz_invalid_type() {
+        for type_ in &[
+            "boolean",
+            "float32",
+            "float64",
+            "int8",
+            "uint8",
+            "int16",
+            "uint16",
+            "int32",
+            "uint32",
+            "string",
+            "timestamp",
+        ] {
+            assert_invalid_fuzz(json!({ "type": type_ }));
+            assert_invalid_fuzz(json!({ "type": type_, "nullable": true }));
+        }
+    }
+
+    #[test]
+    fn test_fuzz_invalid_enum() {
+        assert_invalid_fuzz(json!({ "enum": ["a", "b", "c"] }));
+        assert_invalid_fuzz(json!({ "enum": ["", "a"], "nullable": true }));
+    }
+
+    #[test]
+    fn test_fuzz_invalid_ref() {
+        assert_invalid_fuzz(json!({
+            "definitions": {
+                "a": { "type": "timestamp" },
+                "b": { "ref": "a", "nullable": true },
+            },
+            "elements": { "ref": "b" },
+        }));
+    }
+
+    #[test]
+    fn test_fuzz_invalid_properties() {
+        assert_invalid_fuzz(json!({
+            "properties": {
+                "a": { "type": "uint8" },
+                "b": { "elements": { "type": "string" }},
+            },
+            "optionalProperties": {
+                "c": { "values": { "enum": ["x", "y"] }},
+            },
+            "nullable": true,
+        }));
+
+        assert_invalid_fuzz(json!({
+            "optionalProperties": {
+                "a": { "type": "uint8" },
+            },
+            "additionalProperties": true,
+        }));
+    }
+
+    #[test]
+    fn test_fuzz_invalid_discriminator() {
+        assert_invalid_fuzz(json!({
+            "discriminator": "version",
+            "mapping": {
+                "v1": {
+                    "properties": {
+                        "foo": { "type": "string" },
+                    }
+                },
+                "v2": {
+                    "optionalProperties": {
+                        "foo": { "type": "uint8" },
+                    },
+                    "additionalProperties": true,
+                }
+            },
+        }));
+    }
+
+    fn assert_invalid_fuzz(schema: Value) {
+        use rand::SeedableRng;
+
+        let mut rng = rand_pcg::Pcg32::seed_from_u64(8927);
+        let schema = Schema::from_serde_schema(serde_json::from_value(schema).unwrap()).unwrap();
+
+        for _ in 0..1000 {
+            let invalid = fuzz_invalid(&schema, &mut rng).unwrap();
+            let errors: Vec<_> = jtd::validate(&schema, &invalid.instance, Default::default())
+                .unwrap()
+                .into_iter()
+                .map(|e| e.into_owned_paths())
+                .collect();
+
+            assert_eq!(
+                vec![(invalid.instance_path, invalid.schema_path)],
+                errors,
+                "{}",
+                invalid.instance
+            );
+        }
+    }
+}
@@ -0,0 +1,170 @@
+use crate::{CompiledFuzzer, FuzzConfig};
+use jtd::Schema;
+use serde_json::Value;
+
+/// An endless iterator of random JSON values satisfying a schema.
+///
+/// A `Fuzzer` owns everything it needs to generate values: a compiled copy of
+/// the schema, the configuration, and the RNG. So unlike calling
+/// [`fuzz`](crate::fuzz) in a loop, it can be passed around, stored, and used
+/// in iterator pipelines freely. Each value is generated as it's asked for, and
+/// is exactly what calling [`fuzz_with_config`](crate::fuzz_with_config) with
+/// the same RNG would have generated.
+///
+/// ```
+/// use serde_json::json;
+/// use rand::SeedableRng;
+///
+/// let schema = jtd::Schema::from_serde_schema(serde_json::from_value(json!({
+///     "elements": { "type": "uint8" }
+/// })).unwrap()).unwrap();
+///
+/// let rng = rand_pcg::Pcg32::seed_from_u64(8927);
+/// let lens: Vec<_> = jtd_fuzz::Fuzzer::new(&schema, rng)
+///     .map(|value| value.as_array().unwrap().len())
+///     .take(5)
+///     .collect();
+///
+/// assert_eq!(lens, vec![3, 4, 4, 1, 0]);
+/// ```
+#[derive(Clone, Debug)]
+pub struct Fuzzer<R> {
+    fuzzer: CompiledFuzzer,
+    rng: R,
+}
+
+impl<R: rand::Rng> Fuzzer<R> {
+    /// Constructs an iterator of values satisfying `schema`, using the default
+    /// configuration.
+    ///
+    /// See [`CompiledFuzzer::new`] for when this panics.
+    pub fn new(schema: &Schema, rng: R) -> Self {
+        CompiledFuzzer::new(schema).iter(rng)
+    }
+
+    /// Sets the configuration to generate values with.
+    pub fn with_config(mut self, config: FuzzConfig) -> Self {
+        self.fuzzer = self.fuzzer.with_config(config);
+        self
+    }
+
+    /// Converts this iterator into a [`futures::Stream`] of the same values.
+    ///
+    /// This is only available with the `futures` feature enabled. The stream
+    /// is always ready, so awaiting the next value generates it right away.
+    #[cfg(feature = "futures")]
+    pub fn into_stream(self) -> FuzzStream<R> {
+        FuzzStream(self)
+    }
+}
+
+impl CompiledFuzzer {
+    /// Converts this into an endless iterator of values, generated with `rng`.
+    ///
+    /// See [`Fuzzer`] for details.
+    pub fn iter<R: rand::Rng>(self, rng: R) -> Fuzzer<R> {
+        Fuzzer { fuzzer: self, rng }
+    }
+}
+
+impl<R: rand::Rng> Iterator for Fuzzer<R> {
+    type Item = Value;
+
+    fn next(&mut self) -> Option<Value> {
+        Some(self.fuzzer.fuzz(&mut self.rng))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (usize::MAX, None)
+    }
+}
+
+/// An endless [`futures::Stream`] of random JSON values satisfying a schema.
+///
+/// This is only available with the `futures` feature enabled, and is returned
+/// by [`Fuzzer::into_stream`].
+///
+/// ```
+/// use futures::StreamExt;
+/// use serde_json::json;
+/// use rand::SeedableRng;
+///
+/// let schema = jtd::Schema::from_serde_schema(serde_json::from_value(json!({
+///     "type": "boolean"
+/// })).unwrap()).unwrap();
+///
+/// let rng = rand_pcg::Pcg32::seed_from_u64(8927);
+/// let stream = jtd_fuzz::Fuzzer::new(&schema, rng).into_stream();
+///
+/// let values: Vec<_> = futures::executor::block_on(stream.take(3).collect());
+/// assert_eq!(values.len(), 3);
+/// ```
+#[cfg(feature = "futures")]
+#[derive(Clone, Debug)]
+pub struct FuzzStream<R>(Fuzzer<R>);
+
+#[cfg(feature = "futures")]
+impl<R: rand::Rng + Unpin> futures::Stream for FuzzStream<R> {
+    type Item = Value;
+
+    fn poll_next(
+        self: std::pin::Pin<&mut Self>,
+        _cx: &mut std::task::Context<'_>,
+    ) -> std::task::Poll<Option<Value>> {
+        std::task::Poll::Ready(self.get_mut().0.next())
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.0.size_hint()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fuzz_with_config;
+    use rand::SeedableRng;
+    use serde_json::json;
+
+    #[test]
+    fn test_fuzzer() {
+        let schema = json!({
+            "definitions": {
+                "node": {
+                    "properties": {
+                        "children": { "elements": { "ref": "node" }},
+                    },
+                },
+            },
+            "optionalProperties": {
+                "node": { "ref": "node" },
+                "any": {},
+            },
+        });
+        let schema = Schema::from_serde_schema(serde_json::from_value(schema).unwrap()).unwrap();
+        let config = FuzzConfig::new().with_optional_property_probability(0.9);
+
+        let mut rng = rand_pcg::Pcg32::seed_from_u64(8927);
+        let fuzzer = Fuzzer::new(&schema, rng.clone()).with_config(config.clone());
+
+        for value in fuzzer.take(100) {
+            assert_eq!(fuzz_with_config(&schema, &config, &mut rng), value);
+        }
+    }
+
+    #[cfg(feature = "futures")]
+    #[test]
+    fn test_fuzz_stream() {
+        use futures::StreamExt;
+
+        let schema = Schema::from_serde_schema(serde_json::from_value(json!({})).unwrap()).unwrap();
+
+        let rng = rand_pcg::Pcg32::seed_from_u64(8927);
+        let expected: Vec<_> = Fuzzer::new(&schema, rng.clone()).take(100).collect();
+
+        let stream = Fuzzer::new(&schema, rng).into_stream();
+        let actual: Vec<_> = futures::executor::block_on(stream.take(100).collect());
+
+        assert_eq!(expected, actual);
+    }
+}
@@ -29,25 +29,88 @@
 //! }));
 //! ```
 
+mod analysis;
+#[cfg(any(feature = "cbor", feature = "msgpack"))]
+mod binary;
+mod check;
+mod compiled;
+mod config;
+mod dates;
+mod encode;
+mod formats;
+mod hints;
+mod invalid;
+mod iter;
+mod lint;
+mod numbers;
+mod pattern;
+#[cfg(feature = "proptest")]
+mod strategy;
+mod stream;
+mod strings;
+#[cfg(test)]
+mod testing;
+mod timestamps;
+#[cfg(feature = "arbitrary")]
+mod unstructured;
+mod words;
+
+pub use analysis::{min_depths, UnsatisfiableError};
+#[cfg(feature = "cbor")]
+pub use binary::{fuzz_to_cbor, fuzz_to_cbor_with_config, CborTimestamps};
+#[cfg(feature = "msgpack")]
+pub use binary::{fuzz_to_msgpack, fuzz_to_msgpack_with_config};
+pub use check::{try_fuzz, try_fuzz_with_config, FuzzError};
+pub use compiled::CompiledFuzzer;
+pub use config::FuzzConfig;
+pub use encode::{encode, encode_with_config, EncodeError};
+pub use hints::{HintGenerator, HintRegistry};
+pub use invalid::{fuzz_invalid, fuzz_invalid_with_config, InvalidInstance};
+#[cfg(feature = "futures")]
+pub use iter::FuzzStream;
+pub use iter::Fuzzer;
+pub use lint::{lint_hints, lint_hints_with_config, HintError};
+pub use numbers::{FloatDistribution, NumberStrategy};
+#[cfg(feature = "proptest")]
+pub use strategy::{strategy, strategy_with_config};
+pub use stream::{
+    fuzz_to_serializer, fuzz_to_serializer_with_config, fuzz_to_writer, fuzz_to_writer_with_config,
+};
+pub use strings::CharClass;
+#[cfg(feature = "arbitrary")]
+pub use unstructured::{fuzz_unstructured, fuzz_unstructured_with_config};
+
+use config::{gen_length, gen_probability};
+use hints::SharedHint;
 use jtd::{Schema, Type};
-use rand::seq::IteratorRandom;
+use pattern::Pattern;
+use rand::seq::{IteratorRandom, SliceRandom};
 use serde_json::Value;
-use std::collections::{BTreeMap, BTreeSet};
-
-// Max length when generating "sequences" of things, such as strings, arrays,
-// and objects.
-const MAX_SEQ_LENGTH: u8 = 8;
+use std::collections::BTreeMap;
+use std::convert::TryFrom;
 
 // Key in metadata that, if present and one of the recognized values, will
 // result in a specific sort of data being produced instead of the generic
 // default.
-const METADATA_KEY_FUZZ_HINT: &'static str = "fuzzHint";
+const METADATA_KEY_FUZZ_HINT: &str = "fuzzHint";
+
+// Key in metadata that, if present and a supported regular expression, will
+// result in strings that match it being produced. This takes precedence over
+// METADATA_KEY_FUZZ_HINT.
+const METADATA_KEY_FUZZ_PATTERN: &str = "fuzzPattern";
 
 /// Generates a single random JSON value satisfying a given schema.
 ///
 /// The generated output is purely a function of the given schema and RNG. It is
 /// guaranteed that the returned data satisfies the given schema.
 ///
+/// Some valid schemas have no finite instances at all, and this function will
+/// never return for them. Use [`min_depths`] to check for such schemas ahead of
+/// time.
+///
+/// This function assumes that the schema is valid, and may panic if it isn't.
+/// To get an error instead, use [`try_fuzz`].
+///
 /// # Invariants for generated data
 ///
 /// The output of this function is not guaranteed to remain the same between
@@ -64,11 +127,22 @@ const METADATA_KEY_FUZZ_HINT: &'static str = "fuzzHint";
 /// * No more than seven "extra" properties will be added for schemas with
 ///   `additionalProperties`.
 ///
-/// * Generated strings will be entirely printable ASCII.
+/// * `nullable` schemas will be `null` half the time, and each of
+///   `optionalProperties` will be present half the time.
+///
+/// * Recursive definitions will be followed at most three times before the
+///   rest of the value is made as small as possible. See
+///   [`FuzzConfig::with_max_recursion_depth`] for details.
+///
+/// * Generated strings will be entirely printable ASCII, not including the
+///   non-printable DEL character.
 ///
 /// * Generated timestamps will have a random offset from UTC. These offsets
 ///   will not necessarily be "historical"; some offsets may never have been
-///   used in the real world.
+///   used in the real world. They will be at most fourteen hours from UTC,
+///   represent a time between 1901 and 2038, and have no fractional seconds.
+///
+/// To change the limits and probabilities above, use [`fuzz_with_config`].
 ///
 /// # Using `fuzzHint`
 ///
@@ -94,10 +168,14 @@ const METADATA_KEY_FUZZ_HINT: &'static str = "fuzzHint";
 ///
 /// `fuzzHint` will only be honored for schemas with `type` of `string`. It will
 /// not be honored for empty schemas. If `fuzzHint` does not have one of the
-/// values listed below, then its value will be ignored.
+/// values listed below, then its value will be ignored. [`lint_hints`] finds
+/// hints that will be ignored, such as misspelled ones.
 ///
-/// The possible values for `fuzzHint` are:
+/// The possible values for `fuzzHint` are listed below. You can add values of
+/// your own by registering them in a [`HintRegistry`], and passing it to
+/// [`FuzzConfig::with_hints`].
 ///
+/// * [`en_us/addresses/address`][`faker_rand::en_us::addresses::Address`]
 /// * [`en_us/addresses/city_name`][`faker_rand::en_us::addresses::CityName`]
 /// * [`en_us/addresses/division_abbreviation`][`faker_rand::en_us::addresses::DivisionAbbreviation`]
 /// * [`en_us/addresses/division`][`faker_rand::en_us::addresses::Division`]
@@ -137,13 +215,329 @@ const METADATA_KEY_FUZZ_HINT: &'static str = "fuzzHint";
 /// * [`lorem/paragraph`][`faker_rand::lorem::Paragraph`]
 /// * [`lorem/paragraphs`][`faker_rand::lorem::Paragraphs`]
 ///
+/// As well as these, for strings in well-known formats:
+///
+/// * `encoding/base64`: base64 with padding, like `aGk=`
+/// * `encoding/base64url`: URL-safe base64 without padding, like `aGk`
+/// * `hash/md5`: 32 lowercase hex digits
+/// * `hash/sha1`: 40 lowercase hex digits
+/// * `hash/sha256`: 64 lowercase hex digits
+/// * `internet/cidr`: an IPv4 or IPv6 network, like `10.0.0.0/8` or `2001:db8::/32`
+/// * `internet/hostname`: a hostname, like `api.example.com`
+/// * `internet/ipv4`: an IPv4 address, like `192.168.0.1`
+/// * `internet/ipv6`: an IPv6 address, like `2001:db8::1`
+/// * `internet/mac_address`: a MAC address, like `00:1a:2b:3c:4d:5e`
+/// * `internet/mime_type`: a common MIME type, like `application/json`
+/// * `internet/uri`: a `mailto`, `urn:uuid`, `tel`, or URL URI
+/// * `internet/url`: an `http` or `https` URL
+/// * `software/semver`: a semantic version, like `1.2.3` or `0.4.0-rc.1`
+/// * `uuid/v4`: a random UUID
+/// * `uuid/v7`: a time-ordered UUID, with a timestamp between 1970 and 2038
+///
+/// And for dates, times, and durations that aren't full RFC3339 timestamps:
+///
+/// * `datetime/date`: an RFC3339 date, like `2024-02-29`
+/// * `datetime/duration`: an ISO 8601 duration, like `P1Y2M3DT4H5M6S` or `P3W`
+/// * `datetime/time`: an RFC3339 time, without fractional seconds, like `23:59:01`
+/// * `datetime/unix_millis`: milliseconds since the Unix epoch, like `1709164800000`
+/// * `datetime/unix_seconds`: seconds since the Unix epoch, like `1709164800`
+/// * `strftime:PATTERN`: a date and time formatted with a
+///   [`chrono` strftime pattern][`chrono::format::strftime`], like
+///   `strftime:%d/%m/%Y` for `29/02/2024`
+///
+/// Dates and times are from between 1901 and 2038, like timestamps are by
+/// default.
+///
 /// New acceptable values for `fuzzHint` may be added to this crate within the
 /// same major version.
+///
+/// # Using `fuzzPattern`
+///
+/// For strings that follow a pattern of their own, such as identifiers, use the
+/// `fuzzPattern` metadata property instead. Its value is a regular expression,
+/// in the syntax of the [`regex`](https://docs.rs/regex) crate, and generated
+/// strings match it:
+///
+/// ```
+/// use serde_json::json;
+/// use rand::SeedableRng;
+///
+/// let schema = jtd::Schema::from_serde_schema(serde_json::from_value(json!({
+///     "type": "string",
+///     "metadata": {
+///         "fuzzPattern": "^ORD-[0-9]{8}$"
+///     }
+/// })).unwrap()).unwrap();
+///
+/// let mut rng = rand_pcg::Pcg32::seed_from_u64(8927);
+/// let order_id = jtd_fuzz::fuzz(&schema, &mut rng);
+/// let order_id = order_id.as_str().unwrap();
+///
+/// assert!(order_id.starts_with("ORD-"));
+/// assert_eq!(order_id.len(), 12);
+/// ```
+///
+/// Generated strings are whole matches of the pattern, so they match whether
+/// or not the pattern is anchored. Character classes, repetition, alternation,
+/// and groups are supported. Repetitions without an upper bound, like `*` and
+/// `+`, repeat at most as many more times than their minimum as strings may
+/// have characters. The anchors `^` and `$` are supported where nothing could
+/// come before or after them, respectively; patterns with other anchors, or
+/// with word boundaries, are ignored, as are patterns that don't parse.
+/// Patterns whose bounded repetitions would generate strings of more than
+/// 10,000 characters, like `(a{1000}){1000}`, are ignored too.
+///
+/// Like `fuzzHint`, `fuzzPattern` is only honored for schemas with `type` of
+/// `string`. If a schema has both, `fuzzPattern` takes precedence.
+/// [`lint_hints`] finds patterns that will be ignored.
 pub fn fuzz<R: rand::Rng>(schema: &Schema, rng: &mut R) -> Value {
-    fuzz_with_root(schema, rng, schema)
+    fuzz_with_config(schema, &FuzzConfig::default(), rng)
 }
 
-fn fuzz_with_root<R: rand::Rng>(root: &Schema, rng: &mut R, schema: &Schema) -> Value {
+/// Generates a single random JSON value satisfying a given schema, using the
+/// given configuration.
+///
+/// This is like [`fuzz`], except that the lengths of generated strings, arrays,
+/// and objects, as well as how often `null` and optional properties are
+/// generated, are controlled by `config` instead of the defaults.
+///
+/// ```
+/// use serde_json::json;
+/// use rand::SeedableRng;
+///
+/// let schema = jtd::Schema::from_serde_schema(serde_json::from_value(json!({
+///     "elements": { "type": "uint8" }
+/// })).unwrap()).unwrap();
+///
+/// let config = jtd_fuzz::FuzzConfig::new().with_elements_length(3..=3);
+///
+/// let mut rng = rand_pcg::Pcg32::seed_from_u64(8927);
+/// let instance = jtd_fuzz::fuzz_with_config(&schema, &config, &mut rng);
+/// assert_eq!(instance.as_array().unwrap().len(), 3);
+/// ```
+pub fn fuzz_with_config<R: rand::Rng>(schema: &Schema, config: &FuzzConfig, rng: &mut R) -> Value {
+    fuzz_with_root(&mut Context::new(config, schema), rng, schema)
+}
+
+/// Generates a single random JSON value satisfying one of a schema's
+/// definitions.
+///
+/// Any `ref`s in the definition are resolved against `root`'s definitions.
+/// The value generated is exactly what [`fuzz`] would generate for a copy of
+/// `root` with its root replaced with `{ "ref": name }`, which is handy for
+/// schemas that are mostly a collection of definitions.
+///
+/// Panics if `root` has no definition named `name`.
+///
+/// ```
+/// use serde_json::json;
+/// use rand::SeedableRng;
+///
+/// let schema = jtd::Schema::from_serde_schema(serde_json::from_value(json!({
+///     "definitions": {
+///         "user": {
+///             "properties": {
+///                 "name": { "type": "string" },
+///                 "friends": { "elements": { "ref": "user" }}
+///             }
+///         }
+///     }
+/// })).unwrap()).unwrap();
+///
+/// let mut rng = rand_pcg::Pcg32::seed_from_u64(8927);
+/// let user = jtd_fuzz::fuzz_definition(&schema, "user", &mut rng);
+/// assert!(user["friends"].is_array());
+/// ```
+pub fn fuzz_definition<R: rand::Rng>(root: &Schema, name: &str, rng: &mut R) -> Value {
+    fuzz_definition_with_config(root, name, &FuzzConfig::default(), rng)
+}
+
+/// Generates a single random JSON value satisfying one of a schema's
+/// definitions, using the given configuration.
+///
+/// See [`fuzz_definition`] and [`fuzz_with_config`] for details.
+pub fn fuzz_definition_with_config<R: rand::Rng>(
+    root: &Schema,
+    name: &str,
+    config: &FuzzConfig,
+    rng: &mut R,
+) -> Value {
+    let (name, definition) = root
+        .definitions()
+        .get_key_value(name)
+        .unwrap_or_else(|| panic!("no such definition: {}", name));
+
+    // Generate the definition as if it had been reached through a ref, so that
+    // refs back to it count towards recursion depth.
+    let mut cx = Context::new(config, root);
+    cx.refs.push(name);
+    fuzz_with_root(&mut cx, rng, definition)
+}
+
+/// Generates the `n`th value in a deterministic sequence of values for a
+/// schema.
+///
+/// Each value in the sequence is generated from its own RNG, which is derived
+/// from `seed` and `n` alone (see [`nth_rng`]). This means any one value can be
+/// reproduced without generating the values that come before it. This is how
+/// the `jtd-fuzz` CLI generates values, so `fuzz_nth(&schema, seed, n)` returns
+/// the same value as the line at index `n` of `jtd-fuzz --seed <seed>`.
+///
+/// ```
+/// use serde_json::json;
+///
+/// let schema = jtd::Schema::from_serde_schema(serde_json::from_value(json!({
+///     "elements": { "type": "uint8" }
+/// })).unwrap()).unwrap();
+///
+/// assert_eq!(
+///     jtd_fuzz::fuzz_nth(&schema, 8927, 48213),
+///     jtd_fuzz::fuzz_nth(&schema, 8927, 48213),
+/// );
+/// ```
+pub fn fuzz_nth(schema: &Schema, seed: u64, n: u64) -> Value {
+    fuzz_nth_with_config(schema, &FuzzConfig::default(), seed, n)
+}
+
+/// Generates the `n`th value in a deterministic sequence of values for a
+/// schema, using the given configuration.
+///
+/// See [`fuzz_nth`] for details.
+pub fn fuzz_nth_with_config(schema: &Schema, config: &FuzzConfig, seed: u64, n: u64) -> Value {
+    fuzz_with_config(schema, config, &mut nth_rng(seed, n))
+}
+
+/// Returns the RNG used to generate the `n`th value in a sequence of values
+/// started from `seed`.
+///
+/// [`fuzz_nth`] uses this RNG with [`fuzz`]. It's exposed so that the same
+/// sequence can be generated with other functions, like [`fuzz_invalid`].
+pub fn nth_rng(seed: u64, n: u64) -> impl rand::Rng + Clone {
+    use rand::{Rng, SeedableRng};
+
+    // PCG supports many independent streams from the same state, so give each
+    // value its own stream.
+    let state = rand_pcg::Pcg32::seed_from_u64(seed).gen();
+    rand_pcg::Pcg32::new(state, n)
+}
+
+// State carried along while generating a single value.
+struct Context<'a> {
+    config: &'a FuzzConfig,
+    root: &'a Schema,
+
+    // The definitions that have been followed, via ref, to get to the schema
+    // currently being generated.
+    refs: Vec<&'a str>,
+
+    // How many of the entries in refs are a definition that was already in refs
+    // when it was followed; i.e. how deeply we've recursed.
+    recursion_depth: usize,
+
+    // The minimum depth of each of root's definitions. This is only needed once
+    // recursion_depth is exhausted, so it's computed lazily.
+    min_depths: Option<analysis::MinDepths>,
+
+    // The generator for each fuzzPattern seen so far, or None if it can't be
+    // used. Parsing a pattern is much slower than generating a string from
+    // it, so each is only parsed once per value.
+    patterns: BTreeMap<&'a str, Option<SharedHint>>,
+
+    // The same, for each fuzzHint. Most hints are only looked up, but strftime
+    // hints are parsed too.
+    hints: BTreeMap<&'a str, Option<SharedHint>>,
+}
+
+impl<'a> Context<'a> {
+    fn new(config: &'a FuzzConfig, root: &'a Schema) -> Self {
+        Self {
+            config,
+            root,
+            refs: Vec::new(),
+            recursion_depth: 0,
+            min_depths: None,
+            patterns: BTreeMap::new(),
+            hints: BTreeMap::new(),
+        }
+    }
+
+    // Whether we've recursed too deeply, and so should be steering towards the
+    // smallest possible instances.
+    fn exhausted(&self) -> bool {
+        self.recursion_depth > self.config.max_recursion_depth
+    }
+
+    fn gen_null<R: rand::Rng>(&self, rng: &mut R, nullable: bool) -> bool {
+        nullable
+            && (self.exhausted() || gen_probability(self.config, rng, self.config.null_probability))
+    }
+
+    // The variants of a discriminator that may be generated.
+    fn variants<'b>(
+        &mut self,
+        mapping: &'b BTreeMap<String, Schema>,
+    ) -> Vec<(&'b String, &'b Schema)> {
+        if !self.exhausted() {
+            return mapping.iter().collect();
+        }
+
+        // Choose among the variants that are quickest to finish generating, so
+        // that recursive schemas will terminate.
+        let min_depths = self.min_depths();
+        let depths: BTreeMap<_, _> = mapping
+            .iter()
+            .map(|(k, s)| (k, analysis::min_depth(min_depths, s)))
+            .collect();
+        let min_depth = depths.values().flatten().min().copied();
+
+        mapping
+            .iter()
+            .filter(|(k, _)| depths[k] == min_depth)
+            .collect()
+    }
+
+    fn min_depths(&mut self) -> &analysis::MinDepths {
+        let root = self.root;
+        self.min_depths
+            .get_or_insert_with(|| analysis::compute_min_depths(root))
+    }
+
+    // Like string_generator, but with patterns and hints resolved only the
+    // first time they're seen.
+    fn string_generator(
+        &mut self,
+        type_: &Type,
+        metadata: &'a BTreeMap<String, Value>,
+    ) -> Option<SharedHint> {
+        let config = self.config;
+        let pattern = string_metadata(type_, metadata, METADATA_KEY_FUZZ_PATTERN);
+        let hint = string_metadata(type_, metadata, METADATA_KEY_FUZZ_HINT);
+
+        pattern
+            .and_then(|pattern| {
+                self.patterns
+                    .entry(pattern)
+                    .or_insert_with(|| pattern_generator(config, pattern))
+                    .clone()
+            })
+            .or_else(|| {
+                hint.and_then(|hint| {
+                    self.hints
+                        .entry(hint)
+                        .or_insert_with(|| config.hints.resolve(hint))
+                        .clone()
+                })
+            })
+    }
+}
+
+fn fuzz_with_root<'a, R: rand::Rng>(
+    cx: &mut Context<'a>,
+    rng: &mut R,
+    schema: &'a Schema,
+) -> Value {
+    let config = cx.config;
+
     match schema {
         Schema::Empty { .. } => {
             // Generate one of null, boolean, uint8, float64, string, the
@@ -157,64 +551,41 @@ fn fuzz_with_root<R: rand::Rng>(root: &Schema, rng: &mut R, schema: &Schema) ->
             // schemas. But those empty schemas in turn will not contain further
             // empty schemas.
             //
-            // Doing so helps us avoid overflowing the stack.
-            let range_max_value = if root as *const _ == schema as *const _ {
-                7 // 0 through 6
-            } else {
-                5 // 0 through 4
-            };
-
-            let val = rng.gen_range(0..range_max_value);
-            match val {
-                // 0-4 are cases we will always potentially generate.
-                0 => Value::Null,
-                1 => rng.gen::<bool>().into(),
-                2 => rng.gen::<u8>().into(),
-                3 => rng.gen::<f64>().into(),
-                4 => fuzz_string(rng).into(),
-
+            // Doing so helps us avoid overflowing the stack. For the same
+            // reason, we also stick to the primitive cases once we've recursed
+            // too deeply.
+            match rng.gen_range(0..empty_cases(cx, schema)) {
                 // All the following cases are "recursive" cases. See above for
                 // why it's important these come after the "primitive" cases.
-                5 => {
-                    let schema = Schema::Elements {
-                        metadata: Default::default(),
-                        definitions: Default::default(),
-                        nullable: false,
-                        elements: Box::new(Schema::Empty {
-                            metadata: Default::default(),
-                            definitions: Default::default(),
-                        }),
-                    };
-
-                    fuzz(&schema, rng)
-                }
-
-                6 => {
-                    let schema = Schema::Values {
-                        metadata: Default::default(),
-                        definitions: Default::default(),
-                        nullable: false,
-                        values: Box::new(Schema::Empty {
-                            metadata: Default::default(),
-                            definitions: Default::default(),
-                        }),
-                    };
-
-                    fuzz(&schema, rng)
-                }
+                5 => fuzz_with_config(&empty_elements_schema(), config, rng),
+                6 => fuzz_with_config(&empty_values_schema(), config, rng),
 
-                _ => unreachable!(),
+                case => fuzz_empty_primitive(config, rng, case),
             }
         }
 
         Schema::Ref {
             ref ref_, nullable, ..
         } => {
-            if *nullable && rng.gen() {
+            if cx.gen_null(rng, *nullable) {
                 return Value::Null;
             }
 
-            fuzz_with_root(root, rng, &root.definitions()[ref_])
+            let recursive = cx.refs.contains(&ref_.as_str());
+            cx.refs.push(ref_);
+            if recursive {
+                cx.recursion_depth += 1;
+            }
+
+            let root = cx.root;
+            let value = fuzz_with_root(cx, rng, &root.definitions()[ref_]);
+
+            cx.refs.pop();
+            if recursive {
+                cx.recursion_depth -= 1;
+            }
+
+            value
         }
 
         Schema::Type {
@@ -223,213 +594,11 @@ fn fuzz_with_root<R: rand::Rng>(root: &Schema, rng: &mut R, schema: &Schema) ->
             nullable,
             ..
         } => {
-            if *nullable && rng.gen() {
+            if cx.gen_null(rng, *nullable) {
                 return Value::Null;
             }
 
-            match type_ {
-                Type::Boolean => rng.gen::<bool>().into(),
-                Type::Float32 => rng.gen::<f32>().into(),
-                Type::Float64 => rng.gen::<f64>().into(),
-                Type::Int8 => rng.gen::<i8>().into(),
-                Type::Uint8 => rng.gen::<u8>().into(),
-                Type::Int16 => rng.gen::<i16>().into(),
-                Type::Uint16 => rng.gen::<u16>().into(),
-                Type::Int32 => rng.gen::<i32>().into(),
-                Type::Uint32 => rng.gen::<u32>().into(),
-                Type::String => {
-                    match metadata.get(METADATA_KEY_FUZZ_HINT).and_then(Value::as_str) {
-                        Some("en_us/addresses/address") => rng
-                            .gen::<faker_rand::en_us::addresses::Address>()
-                            .to_string()
-                            .into(),
-                        Some("en_us/addresses/city_name") => rng
-                            .gen::<faker_rand::en_us::addresses::CityName>()
-                            .to_string()
-                            .into(),
-                        Some("en_us/addresses/division") => rng
-                            .gen::<faker_rand::en_us::addresses::Division>()
-                            .to_string()
-                            .into(),
-                        Some("en_us/addresses/division_abbreviation") => rng
-                            .gen::<faker_rand::en_us::addresses::DivisionAbbreviation>()
-                            .to_string()
-                            .into(),
-                        Some("en_us/addresses/postal_code") => rng
-                            .gen::<faker_rand::en_us::addresses::PostalCode>()
-                            .to_string()
-                            .into(),
-                        Some("en_us/addresses/secondary_address") => rng
-                            .gen::<faker_rand::en_us::addresses::SecondaryAddress>()
-                            .to_string()
-                            .into(),
-                        Some("en_us/addresses/street_address") => rng
-                            .gen::<faker_rand::en_us::addresses::StreetAddress>()
-                            .to_string()
-                            .into(),
-                        Some("en_us/addresses/street_name") => rng
-                            .gen::<faker_rand::en_us::addresses::StreetName>()
-                            .to_string()
-                            .into(),
-                        Some("en_us/company/company_name") => rng
-                            .gen::<faker_rand::en_us::company::CompanyName>()
-                            .to_string()
-                            .into(),
-                        Some("en_us/company/slogan") => rng
-                            .gen::<faker_rand::en_us::company::Slogan>()
-                            .to_string()
-                            .into(),
-                        Some("en_us/internet/domain") => rng
-                            .gen::<faker_rand::en_us::internet::Domain>()
-                            .to_string()
-                            .into(),
-                        Some("en_us/internet/email") => rng
-                            .gen::<faker_rand::en_us::internet::Email>()
-                            .to_string()
-                            .into(),
-                        Some("en_us/internet/username") => rng
-                            .gen::<faker_rand::en_us::internet::Username>()
-                            .to_string()
-                            .into(),
-                        Some("en_us/names/first_name") => rng
-                            .gen::<faker_rand::en_us::names::FirstName>()
-                            .to_string()
-                            .into(),
-                        Some("en_us/names/full_name") => rng
-                            .gen::<faker_rand::en_us::names::FullName>()
-                            .to_string()
-                            .into(),
-                        Some("en_us/names/last_name") => rng
-                            .gen::<faker_rand::en_us::names::LastName>()
-                            .to_string()
-                            .into(),
-                        Some("en_us/names/name_prefix") => rng
-                            .gen::<faker_rand::en_us::names::NamePrefix>()
-                            .to_string()
-                            .into(),
-                        Some("en_us/names/name_suffix") => rng
-                            .gen::<faker_rand::en_us::names::NameSuffix>()
-                            .to_string()
-                            .into(),
-                        Some("en_us/phones/phone_number") => rng
-                            .gen::<faker_rand::en_us::phones::PhoneNumber>()
-                            .to_string()
-                            .into(),
-                        Some("fr_fr/addresses/address") => rng
-                            .gen::<faker_rand::fr_fr::addresses::Address>()
-                            .to_string()
-                            .into(),
-                        Some("fr_fr/addresses/city_name") => rng
-                            .gen::<faker_rand::fr_fr::addresses::CityName>()
-                            .to_string()
-                            .into(),
-                        Some("fr_fr/addresses/division") => rng
-                            .gen::<faker_rand::fr_fr::addresses::Division>()
-                            .to_string()
-                            .into(),
-                        Some("fr_fr/addresses/postal_code") => rng
-                            .gen::<faker_rand::fr_fr::addresses::PostalCode>()
-                            .to_string()
-                            .into(),
-                        Some("fr_fr/addresses/secondary_address") => rng
-                            .gen::<faker_rand::fr_fr::addresses::SecondaryAddress>()
-                            .to_string()
-                            .into(),
-                        Some("fr_fr/addresses/street_address") => rng
-                            .gen::<faker_rand::fr_fr::addresses::StreetAddress>()
-                            .to_string()
-                            .into(),
-                        Some("fr_fr/addresses/street_name") => rng
-                            .gen::<faker_rand::fr_fr::addresses::StreetName>()
-                            .to_string()
-                            .into(),
-                        Some("fr_fr/company/company_name") => rng
-                            .gen::<faker_rand::fr_fr::company::CompanyName>()
-                            .to_string()
-                            .into(),
-                        Some("fr_fr/internet/domain") => rng
-                            .gen::<faker_rand::fr_fr::internet::Domain>()
-                            .to_string()
-                            .into(),
-                        Some("fr_fr/internet/email") => rng
-                            .gen::<faker_rand::fr_fr::internet::Email>()
-                            .to_string()
-                            .into(),
-                        Some("fr_fr/internet/username") => rng
-                            .gen::<faker_rand::fr_fr::internet::Username>()
-                            .to_string()
-                            .into(),
-                        Some("fr_fr/names/first_name") => rng
-                            .gen::<faker_rand::fr_fr::names::FirstName>()
-                            .to_string()
-                            .into(),
-                        Some("fr_fr/names/full_name") => rng
-                            .gen::<faker_rand::fr_fr::names::FullName>()
-                            .to_string()
-                            .into(),
-                        Some("fr_fr/names/last_name") => rng
-                            .gen::<faker_rand::fr_fr::names::LastName>()
-                            .to_string()
-                            .into(),
-                        Some("fr_fr/names/name_prefix") => rng
-                            .gen::<faker_rand::fr_fr::names::NamePrefix>()
-                            .to_string()
-                            .into(),
-                        Some("fr_fr/phones/phone_number") => rng
-                            .gen::<faker_rand::fr_fr::phones::PhoneNumber>()
-                            .to_string()
-                            .into(),
-                        Some("lorem/word") => {
-                            rng.gen::<faker_rand::lorem::Word>().to_string().into()
-                        }
-                        Some("lorem/sentence") => {
-                            rng.gen::<faker_rand::lorem::Sentence>().to_string().into()
-                        }
-                        Some("lorem/paragraph") => {
-                            rng.gen::<faker_rand::lorem::Paragraph>().to_string().into()
-                        }
-                        Some("lorem/paragraphs") => rng
-                            .gen::<faker_rand::lorem::Paragraphs>()
-                            .to_string()
-                            .into(),
-
-                        _ => fuzz_string(rng).into(),
-                    }
-                }
-                Type::Timestamp => {
-                    use chrono::TimeZone;
-
-                    // We'll generate timestamps with some random seconds offset
-                    // from UTC. Most of these random offsets will never have
-                    // been used historically, but they can nonetheless be used
-                    // in valid RFC3339 timestamps.
-                    //
-                    // Although timestamp_millis accepts an i64, not all values
-                    // in that range are permissible. The i32 range is entirely
-                    // safe.
-                    //
-                    // However, UTC offsets present a practical complication:
-                    //
-                    // Java's java.time.ZoneOffset restricts offsets to no more
-                    // than 18 hours from UTC:
-                    //
-                    // https://docs.oracle.com/javase/8/docs/api/java/time/ZoneOffset.html
-                    //
-                    // .NET's System.DateTimeOffset restricts offsets to no more
-                    // than 14 hours from UTC:
-                    //
-                    // https://docs.microsoft.com/en-us/dotnet/api/system.datetimeoffset.tooffset?view=net-5.0
-                    //
-                    // To make jtd-fuzz work out of the box with these
-                    // ecosystems, we will limit ourselves to the most selective
-                    // of these time ranges.
-                    let max_offset = 14 * 60 * 60;
-                    chrono::FixedOffset::east(rng.gen_range(-max_offset..=max_offset))
-                        .timestamp(rng.gen::<i32>() as i64, 0)
-                        .to_rfc3339()
-                        .into()
-                }
-            }
+            fuzz_type(cx, rng, metadata, type_)
         }
 
         Schema::Enum {
@@ -437,7 +606,7 @@ fn fuzz_with_root<R: rand::Rng>(root: &Schema, rng: &mut R, schema: &Schema) ->
             nullable,
             ..
         } => {
-            if *nullable && rng.gen() {
+            if cx.gen_null(rng, *nullable) {
                 return Value::Null;
             }
 
@@ -449,12 +618,18 @@ fn fuzz_with_root<R: rand::Rng>(root: &Schema, rng: &mut R, schema: &Schema) ->
             nullable,
             ..
         } => {
-            if *nullable && rng.gen() {
+            if cx.gen_null(rng, *nullable) {
                 return Value::Null;
             }
 
-            (0..rng.gen_range(0..MAX_SEQ_LENGTH))
-                .map(|_| fuzz_with_root(root, rng, elements))
+            let len = if cx.exhausted() {
+                0
+            } else {
+                gen_length(rng, &config.elements_length)
+            };
+
+            (0..len)
+                .map(|_| fuzz_with_root(cx, rng, elements))
                 .collect::<Vec<_>>()
                 .into()
         }
@@ -466,7 +641,7 @@ fn fuzz_with_root<R: rand::Rng>(root: &Schema, rng: &mut R, schema: &Schema) ->
             nullable,
             ..
         } => {
-            if *nullable && rng.gen() {
+            if cx.gen_null(rng, *nullable) {
                 return Value::Null;
             }
 
@@ -476,7 +651,7 @@ fn fuzz_with_root<R: rand::Rng>(root: &Schema, rng: &mut R, schema: &Schema) ->
             required_keys.sort();
 
             for k in required_keys {
-                let v = fuzz_with_root(root, rng, &properties[&k]);
+                let v = fuzz_with_root(cx, rng, &properties[&k]);
                 members.insert(k, v);
             }
 
@@ -484,45 +659,29 @@ fn fuzz_with_root<R: rand::Rng>(root: &Schema, rng: &mut R, schema: &Schema) ->
             optional_keys.sort();
 
             for k in optional_keys {
-                if rng.gen() {
+                // This is phrased so that the smallest random numbers omit
+                // optional properties, the same way they produce nulls and
+                // empty arrays. See strategy() for why that matters.
+                if cx.exhausted()
+                    || gen_probability(config, rng, 1.0 - config.optional_property_probability)
+                {
                     continue;
                 }
 
-                let v = fuzz_with_root(root, rng, &optional_properties[&k]);
+                let v = fuzz_with_root(cx, rng, &optional_properties[&k]);
                 members.insert(k, v);
             }
 
-            if *additional_properties {
-                // Go's encoding/json package, which implements JSON
-                // serialization/deserialization, is case-insensitive on inputs.
-                //
-                // In order to generate fuzzed data that's compatible with Go,
-                // we'll avoid generating "additional" properties that are
-                // case-insensitively equal to any required or optional property
-                // from the schema.
-                //
-                // Since we'll only generate ASCII properties here, we don't
-                // need to worry about implementing proper Unicode folding.
-                let defined_properties_lowercase: BTreeSet<_> = properties
-                    .keys()
-                    .chain(optional_properties.keys())
-                    .map(|s| s.to_lowercase())
-                    .collect();
-
-                for _ in 0..rng.gen_range(0..MAX_SEQ_LENGTH) {
-                    let key = fuzz_string(rng);
-
-                    if !defined_properties_lowercase.contains(&key.to_lowercase()) {
-                        members.insert(
-                            key,
-                            fuzz(
-                                &Schema::Empty {
-                                    metadata: Default::default(),
-                                    definitions: Default::default(),
-                                },
-                                rng,
-                            ),
-                        );
+            if *additional_properties && !cx.exhausted() {
+                // Additional properties never collide with defined ones. See
+                // DefinedProperties for why.
+                let defined = strings::DefinedProperties::new(
+                    properties.keys().chain(optional_properties.keys()),
+                );
+
+                for _ in 0..gen_length(rng, &config.additional_properties_length) {
+                    if let Some(key) = strings::fuzz_additional_key(config, rng, &defined) {
+                        members.insert(key, fuzz_with_config(&empty_schema(), config, rng));
                     }
                 }
             }
@@ -538,12 +697,23 @@ fn fuzz_with_root<R: rand::Rng>(root: &Schema, rng: &mut R, schema: &Schema) ->
             nullable,
             ..
         } => {
-            if *nullable && rng.gen() {
+            if cx.gen_null(rng, *nullable) {
                 return Value::Null;
             }
 
-            (0..rng.gen_range(0..MAX_SEQ_LENGTH))
-                .map(|_| (fuzz_string(rng), fuzz_with_root(root, rng, values)))
+            let len = if cx.exhausted() {
+                0
+            } else {
+                gen_length(rng, &config.values_length)
+            };
+
+            (0..len)
+                .map(|_| {
+                    (
+                        strings::fuzz_string(config, rng),
+                        fuzz_with_root(cx, rng, values),
+                    )
+                })
                 .collect::<serde_json::Map<String, Value>>()
                 .into()
         }
@@ -554,13 +724,13 @@ fn fuzz_with_root<R: rand::Rng>(root: &Schema, rng: &mut R, schema: &Schema) ->
             nullable,
             ..
         } => {
-            if *nullable && rng.gen() {
+            if cx.gen_null(rng, *nullable) {
                 return Value::Null;
             }
 
-            let (discriminator_value, sub_schema) = mapping.iter().choose(rng).unwrap();
+            let (discriminator_value, sub_schema) = *cx.variants(mapping).choose(rng).unwrap();
 
-            let mut obj = fuzz_with_root(root, rng, sub_schema);
+            let mut obj = fuzz_with_root(cx, rng, sub_schema);
             obj.as_object_mut().unwrap().insert(
                 discriminator.to_owned(),
                 discriminator_value.to_owned().into(),
@@ -570,16 +740,131 @@ fn fuzz_with_root<R: rand::Rng>(root: &Schema, rng: &mut R, schema: &Schema) ->
     }
 }
 
-fn fuzz_string<R: rand::Rng>(rng: &mut R) -> String {
-    (0..rng.gen_range(0..MAX_SEQ_LENGTH))
-        .map(|_| rng.gen_range(32u8..=127u8) as char)
-        .collect::<String>()
+// How many cases to choose from when generating an empty schema. Cases 0
+// through 4 are primitive, and 5 and 6 are recursive. See fuzz_with_root.
+fn empty_cases(cx: &Context, schema: &Schema) -> u32 {
+    if std::ptr::eq(cx.root, schema) && !cx.exhausted() {
+        7 // 0 through 6
+    } else {
+        5 // 0 through 4
+    }
+}
+
+// Generates one of the primitive cases of an empty schema.
+fn fuzz_empty_primitive<R: rand::Rng>(config: &FuzzConfig, rng: &mut R, case: u32) -> Value {
+    match case {
+        0 => Value::Null,
+        1 => rng.gen::<bool>().into(),
+        2 => numbers::fuzz_int::<u8, _>(config, rng).into(),
+        3 => numbers::fuzz_float64(config, rng).into(),
+        4 => strings::fuzz_string(config, rng).into(),
+        _ => unreachable!(),
+    }
+}
+
+fn fuzz_type<'a, R: rand::Rng>(
+    cx: &mut Context<'a>,
+    rng: &mut R,
+    metadata: &'a BTreeMap<String, Value>,
+    type_: &Type,
+) -> Value {
+    let generator = cx.string_generator(type_, metadata);
+    fuzz_type_with_hint(
+        cx.config,
+        rng,
+        type_,
+        generator.as_ref().map(SharedHint::get),
+    )
+}
+
+// Returns a string schema's fuzzHint or fuzzPattern, if it has one.
+fn string_metadata<'a>(
+    type_: &Type,
+    metadata: &'a BTreeMap<String, Value>,
+    key: &str,
+) -> Option<&'a str> {
+    if *type_ != Type::String {
+        return None;
+    }
+
+    metadata.get(key).and_then(Value::as_str)
+}
+
+// Returns the generator for a string schema's fuzzPattern or fuzzHint, if it
+// has one that can be used. Patterns that can't be used are ignored, just like
+// unknown hints are.
+fn string_generator(
+    config: &FuzzConfig,
+    pattern: Option<&str>,
+    hint: Option<&str>,
+) -> Option<SharedHint> {
+    pattern
+        .and_then(|pattern| pattern_generator(config, pattern))
+        .or_else(|| hint.and_then(|hint| config.hints.resolve(hint)))
+}
+
+// Returns the generator for a fuzzPattern, if it can be used.
+fn pattern_generator(config: &FuzzConfig, pattern: &str) -> Option<SharedHint> {
+    let max_extra_repeats = u32::try_from(*config.string_length.end()).unwrap_or(u32::MAX);
+    Pattern::parse(pattern, max_extra_repeats)
+        .ok()
+        .map(SharedHint::new)
+}
+
+fn fuzz_type_with_hint<R: rand::Rng>(
+    config: &FuzzConfig,
+    rng: &mut R,
+    type_: &Type,
+    hint: Option<&dyn HintGenerator>,
+) -> Value {
+    match type_ {
+        Type::Boolean => rng.gen::<bool>().into(),
+        Type::Float32 => numbers::fuzz_float32(config, rng).into(),
+        Type::Float64 => numbers::fuzz_float64(config, rng).into(),
+        Type::Int8 => numbers::fuzz_int::<i8, _>(config, rng).into(),
+        Type::Uint8 => numbers::fuzz_int::<u8, _>(config, rng).into(),
+        Type::Int16 => numbers::fuzz_int::<i16, _>(config, rng).into(),
+        Type::Uint16 => numbers::fuzz_int::<u16, _>(config, rng).into(),
+        Type::Int32 => numbers::fuzz_int::<i32, _>(config, rng).into(),
+        Type::Uint32 => numbers::fuzz_int::<u32, _>(config, rng).into(),
+        Type::String => match hint {
+            Some(generator) => generator.generate(rng).into(),
+            None => strings::fuzz_string(config, rng).into(),
+        },
+        Type::Timestamp => timestamps::fuzz_timestamp(config, rng).into(),
+    }
+}
+
+fn empty_schema() -> Schema {
+    Schema::Empty {
+        metadata: Default::default(),
+        definitions: Default::default(),
+    }
+}
+
+fn empty_elements_schema() -> Schema {
+    Schema::Elements {
+        metadata: Default::default(),
+        definitions: Default::default(),
+        nullable: false,
+        elements: Box::new(empty_schema()),
+    }
+}
+
+fn empty_values_schema() -> Schema {
+    Schema::Values {
+        metadata: Default::default(),
+        definitions: Default::default(),
+        nullable: false,
+        values: Box::new(empty_schema()),
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
     use serde_json::json;
+    use std::collections::BTreeSet;
 
     #[test]
     fn test_fuzz_empty() {
@@ -624,6 +909,85 @@ mod tests {
         assert_valid_fuzz(json!({ "type": "timestamp", "nullable": true }));
     }
 
+    #[test]
+    fn test_fuzz_pattern() {
+        use rand::SeedableRng;
+
+        let schema = Schema::from_serde_schema(
+            serde_json::from_value(json!({
+                "properties": {
+                    "ids": {
+                        "elements": {
+                            "type": "string",
+                            "metadata": { "fuzzPattern": "^ORD-[0-9]{4}$" },
+                        },
+                    },
+                    "invalid": {
+                        "type": "string",
+                        "metadata": { "fuzzPattern": "[a-" },
+                    },
+                    "huge": {
+                        "type": "string",
+                        "metadata": { "fuzzPattern": "^(a{1000}){1000}{1000}$" },
+                    },
+                },
+            }))
+            .unwrap(),
+        )
+        .unwrap();
+
+        let config = FuzzConfig::new().with_elements_length(7..=7);
+        let regex = regex::Regex::new("^ORD-[0-9]{4}$").unwrap();
+        let mut rng = rand_pcg::Pcg32::seed_from_u64(8927);
+
+        // Each pattern is parsed once, however many strings are generated from
+        // it, and patterns that can't be used are remembered too.
+        let mut cx = Context::new(&config, &schema);
+        let instance = fuzz_with_root(&mut cx, &mut rng, &schema);
+        assert_eq!(
+            vec![&"[a-", &"^(a{1000}){1000}{1000}$", &"^ORD-[0-9]{4}$"],
+            cx.patterns.keys().collect::<Vec<_>>()
+        );
+        assert!(cx.patterns["[a-"].is_none());
+        assert!(cx.patterns["^(a{1000}){1000}{1000}$"].is_none());
+        assert!(instance["huge"].as_str().unwrap().len() <= 7);
+
+        for id in instance["ids"].as_array().unwrap() {
+            assert!(regex.is_match(id.as_str().unwrap()), "{}", id);
+        }
+    }
+
+    #[test]
+    fn test_fuzz_strftime_hint() {
+        use rand::SeedableRng;
+
+        let schema = Schema::from_serde_schema(
+            serde_json::from_value(json!({
+                "elements": {
+                    "type": "string",
+                    "metadata": { "fuzzHint": "strftime:%d/%m/%Y" },
+                },
+            }))
+            .unwrap(),
+        )
+        .unwrap();
+
+        let config = FuzzConfig::new().with_elements_length(7..=7);
+        let mut rng = rand_pcg::Pcg32::seed_from_u64(8927);
+
+        // Like patterns, each hint is resolved once.
+        let mut cx = Context::new(&config, &schema);
+        let instance = fuzz_with_root(&mut cx, &mut rng, &schema);
+        assert_eq!(
+            vec![&"strftime:%d/%m/%Y"],
+            cx.hints.keys().collect::<Vec<_>>()
+        );
+
+        for date in instance.as_array().unwrap() {
+            chrono::NaiveDate::parse_from_str(date.as_str().unwrap(), "%d/%m/%Y").unwrap();
+        }
+    }
+
     #[test]
     fn test_fuzz_enum() {
         assert_valid_fuzz(json!({ "enum": ["a", "b", "c" ]}));
@@ -680,6 +1044,218 @@ mod tests {
         }));
     }
 
+    #[test]
+    fn test_fuzz_with_config() {
+        use rand::SeedableRng;
+
+        let schema = Schema::from_serde_schema(
+            serde_json::from_value(json!({
+                "properties": {
+                    "a": { "elements": { "type": "string" }},
+                    "b": { "values": { "type": "uint8", "nullable": true }},
+                },
+                "optionalProperties": {
+                    "c": { "type": "uint8" },
+                },
+                "additionalProperties": true,
+            }))
+            .unwrap(),
+        )
+        .unwrap();
+
+        let config = FuzzConfig::new()
+            .with_string_length(10..=12)
+            .with_elements_length(2..=3)
+            .with_values_length(1..=1)
+            .with_additional_properties_length(0..=0)
+            .with_null_probability(1.0)
+            .with_optional_property_probability(0.0);
+
+        let mut rng = rand_pcg::Pcg32::seed_from_u64(8927);
+        for _ in 0..1000 {
+            let instance = fuzz_with_config(&schema, &config, &mut rng);
+            let errors = jtd::validate(&schema, &instance, Default::default()).unwrap();
+            assert!(errors.is_empty(), "{}", instance);
+
+            let obj = instance.as_object().unwrap();
+            assert_eq!(2, obj.len(), "{}", instance);
+
+            let a = obj["a"].as_array().unwrap();
+            assert!((2..=3).contains(&a.len()), "{}", instance);
+            for s in a {
+                assert!(
+                    (10..=12).contains(&s.as_str().unwrap().len()),
+                    "{}",
+                    instance
+                );
+            }
+
+            let b = obj["b"].as_object().unwrap();
+            assert_eq!(1, b.len(), "{}", instance);
+            assert!(b.values().all(Value::is_null), "{}", instance);
+        }
+    }
+
+    #[test]
+    fn test_fuzz_recursive() {
+        // A linked list.
+        assert_valid_fuzz(json!({
+            "definitions": {
+                "node": {
+                    "properties": {
+                        "value": { "type": "uint8" },
+                        "next": { "ref": "node", "nullable": true },
+                    }
+                }
+            },
+            "ref": "node",
+        }));
+
+        // A tree.
+        assert_valid_fuzz(json!({
+            "definitions": {
+                "node": {
+                    "optionalProperties": {
+                        "children": { "elements": { "ref": "node" }},
+                        "attributes": { "values": { "ref": "node" }},
+                    }
+                }
+            },
+            "ref": "node",
+        }));
+
+        // An expression AST, where only some variants terminate.
+        assert_valid_fuzz(json!({
+            "definitions": {
+                "expr": {
+                    "discriminator": "type",
+                    "mapping": {
+                        "add": {
+                            "properties": {
+                                "left": { "ref": "expr" },
+                                "right": { "ref": "expr" },
+                            }
+                        },
+                        "neg": {
+                            "properties": {
+                                "operand": { "ref": "expr" },
+                            }
+                        },
+                        "lit": {
+                            "properties": {
+                                "value": { "type": "int32" },
+                            }
+                        },
+                    }
+                }
+            },
+            "ref": "expr",
+        }));
+    }
+
+    #[test]
+    fn test_fuzz_max_recursion_depth() {
+        use rand::SeedableRng;
+
+        let schema = Schema::from_serde_schema(
+            serde_json::from_value(json!({
+                "definitions": {
+                    "node": {
+                        "elements": { "ref": "node" },
+                    }
+                },
+                "ref": "node",
+            }))
+            .unwrap(),
+        )
+        .unwrap();
+
+        fn depth(value: &Value) -> usize {
+            1 + value
+                .as_array()
+                .unwrap()
+                .iter()
+                .map(depth)
+                .max()
+                .unwrap_or(0)
+        }
+
+        let mut rng = rand_pcg::Pcg32::seed_from_u64(8927);
+        for max_recursion_depth in 0..4 {
+            let config = FuzzConfig::new()
+                .with_elements_length(1..=3)
+                .with_max_recursion_depth(max_recursion_depth);
+
+            for _ in 0..100 {
+                // The root ref isn't recursive, and then each level of nesting
+                // is one more level of recursion. The last level is empty.
+                let instance = fuzz_with_config(&schema, &config, &mut rng);
+                assert_eq!(max_recursion_depth + 2, depth(&instance), "{}", instance);
+            }
+        }
+    }
+
+    #[test]
+    fn test_fuzz_nth() {
+        let schema = Schema::from_serde_schema(
+            serde_json::from_value(json!({
+                "properties": {
+                    "a": { "type": "uint32" },
+                    "b": { "elements": { "type": "string" }},
+                },
+            }))
+            .unwrap(),
+        )
+        .unwrap();
+
+        let values: Vec<_> = (0..100).map(|n| fuzz_nth(&schema, 8927, n)).collect();
+
+        // Each value can be regenerated on its own, in any order.
+        for n in (0..100).rev() {
+            assert_eq!(values[n as usize], fuzz_nth(&schema, 8927, n));
+        }
+
+        // Neighboring indices and seeds give unrelated values.
+        let distinct: BTreeSet<_> = values.iter().map(Value::to_string).collect();
+        assert_eq!(100, distinct.len());
+        assert!((0..100).all(|n| values[n as usize] != fuzz_nth(&schema, 8928, n)));
+    }
+
+    #[test]
+    fn test_fuzz_definition() {
+        use rand::SeedableRng;
+
+        let definitions = json!({
+            "node": {
+                "properties": {
+                    "children": { "elements": { "ref": "node" }},
+                },
+            },
+            "any": {},
+        });
+
+        let root = Schema::from_serde_schema(
+            serde_json::from_value(json!({ "definitions": definitions })).unwrap(),
+        )
+        .unwrap();
+
+        for name in &["node", "any"] {
+            let ref_root = Schema::from_serde_schema(
+                serde_json::from_value(json!({ "definitions": definitions, "ref": name })).unwrap(),
+            )
+            .unwrap();
+
+            let mut rng = rand_pcg::Pcg32::seed_from_u64(8927);
+            let mut ref_rng = rng.clone();
+            for _ in 0..100 {
+                assert_eq!(
+                    fuzz(&ref_root, &mut ref_rng),
+                    fuzz_definition(&root, name, &mut rng)
+                );
+            }
+        }
+    }
+
     fn assert_valid_fuzz(schema: Value) {
         use rand::SeedableRng;
 
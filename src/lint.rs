@@ -0,0 +1,385 @@
+use crate::pattern::Pattern;
+use crate::{FuzzConfig, METADATA_KEY_FUZZ_HINT, METADATA_KEY_FUZZ_PATTERN};
+use jtd::{Schema, Type};
+use serde_json::Value;
+use thiserror::Error;
+
+/// Indicates that a `fuzzHint` or `fuzzPattern` will be ignored.
+///
+/// [`fuzz`](crate::fuzz) ignores any `fuzzHint` or `fuzzPattern` it can't
+/// honor, and generates strings as though it weren't there. [`lint_hints`]
+/// finds these instead, so that a typo in a hint doesn't go unnoticed.
+///
+/// Like [`FuzzError`](crate::FuzzError), each variant carries a path to the
+/// schema with the problem, made of the keywords and names leading to it from
+/// the root schema.
+#[derive(Clone, Debug, PartialEq, Eq, Error)]
+pub enum HintError {
+    /// A `fuzzHint` isn't in the configuration's
+    /// [`HintRegistry`](crate::HintRegistry).
+    #[error("unknown fuzzHint {hint:?} at {schema_path:?}")]
+    UnknownHint {
+        /// A path to the schema with the `fuzzHint`.
+        schema_path: Vec<String>,
+
+        /// The unknown hint.
+        hint: String,
+    },
+
+    /// A `fuzzHint` is on a schema that isn't `{"type": "string"}`, where hints
+    /// are never honored.
+    #[error("fuzzHint {hint:?} on a schema that is not of type string at {schema_path:?}")]
+    MisplacedHint {
+        /// A path to the schema with the `fuzzHint`.
+        schema_path: Vec<String>,
+
+        /// The misplaced hint.
+        hint: String,
+    },
+
+    /// A `fuzzHint` isn't a string, and so can't name a hint.
+    #[error("fuzzHint that is not a string at {schema_path:?}")]
+    NonStringHint {
+        /// A path to the schema with the `fuzzHint`.
+        schema_path: Vec<String>,
+
+        /// The value of the `fuzzHint`.
+        hint: Value,
+    },
+
+    /// A `fuzzPattern` is on a schema that isn't `{"type": "string"}`, where
+    /// patterns are never honored.
+    #[error("fuzzPattern {pattern:?} on a schema that is not of type string at {schema_path:?}")]
+    MisplacedPattern {
+        /// A path to the schema with the `fuzzPattern`.
+        schema_path: Vec<String>,
+
+        /// The misplaced pattern.
+        pattern: String,
+    },
+
+    /// A `fuzzPattern` isn't a regular expression that strings can be
+    /// generated for.
+    #[error("fuzzPattern {pattern:?} cannot be used ({reason}) at {schema_path:?}")]
+    InvalidPattern {
+        /// A path to the schema with the `fuzzPattern`.
+        schema_path: Vec<String>,
+
+        /// The value of the `fuzzPattern`, as JSON if it isn't a string.
+        pattern: String,
+
+        /// Why the pattern can't be used.
+        reason: String,
+    },
+}
+
+/// Finds every `fuzzHint` and `fuzzPattern` in a schema that
+/// [`fuzz`](crate::fuzz) would ignore.
+///
+/// Unlike [`try_fuzz`](crate::try_fuzz), this checks the entire schema,
+/// including definitions that are never used. Problems are returned in the
+/// order they're found: the root schema first, and then each definition in
+/// turn, sorted by name.
+///
+/// ```
+/// use serde_json::json;
+///
+/// let schema = jtd::Schema::from_serde_schema(serde_json::from_value(json!({
+///     "properties": {
+///         "email": {
+///             "type": "string",
+///             "metadata": { "fuzzHint": "en_us/internet/emial" }
+///         }
+///     }
+/// })).unwrap()).unwrap();
+///
+/// assert_eq!(
+///     jtd_fuzz::lint_hints(&schema),
+///     vec![jtd_fuzz::HintError::UnknownHint {
+///         schema_path: vec!["properties".into(), "email".into()],
+///         hint: "en_us/internet/emial".into(),
+///     }],
+/// );
+/// ```
+pub fn lint_hints(schema: &Schema) -> Vec<HintError> {
+    lint_hints_with_config(schema, &FuzzConfig::default())
+}
+
+/// Finds every `fuzzHint` and `fuzzPattern` in a schema that
+/// [`fuzz_with_config`](crate::fuzz_with_config) would ignore, given the same
+/// configuration.
+///
+/// Hints are looked up in the configuration's hint registry, so hints you've
+/// added with [`FuzzConfig::with_hints`] aren't reported. See [`lint_hints`]
+/// for details.
+pub fn lint_hints_with_config(schema: &Schema, config: &FuzzConfig) -> Vec<HintError> {
+    let mut linter = Linter {
+        config,
+        schema_path: Vec::new(),
+        errors: Vec::new(),
+    };
+
+    linter.lint(schema);
+    for (name, definition) in schema.definitions() {
+        linter.lint_at(&["definitions", name], definition);
+    }
+
+    linter.errors
+}
+
+struct Linter<'a> {
+    config: &'a FuzzConfig,
+    schema_path: Vec<String>,
+    errors: Vec<HintError>,
+}
+
+impl<'a> Linter<'a> {
+    fn lint(&mut self, schema: &Schema) {
+        if let Some(hint) = schema.metadata().get(METADATA_KEY_FUZZ_HINT) {
+            self.lint_hint(schema, hint);
+        }
+
+        if let Some(pattern) = schema.metadata().get(METADATA_KEY_FUZZ_PATTERN) {
+            self.lint_pattern(schema, pattern);
+        }
+
+        match schema {
+            Schema::Empty { .. }
+            | Schema::Ref { .. }
+            | Schema::Type { .. }
+            | Schema::Enum { .. } => {}
+
+            Schema::Elements { ref elements, .. } => self.lint_at(&["elements"], elements),
+
+            Schema::Properties {
+                ref properties,
+                ref optional_properties,
+                ..
+            } => {
+                for (k, s) in properties {
+                    self.lint_at(&["properties", k], s);
+                }
+
+                for (k, s) in optional_properties {
+                    self.lint_at(&["optionalProperties", k], s);
+                }
+            }
+
+            Schema::Values { ref values, .. } => self.lint_at(&["values"], values),
+
+            Schema::Discriminator { ref mapping, .. } => {
+                for (k, s) in mapping {
+                    self.lint_at(&["mapping", k], s);
+                }
+            }
+        }
+    }
+
+    fn lint_hint(&mut self, schema: &Schema, hint: &Value) {
+        let schema_path = self.schema_path.clone();
+
+        let hint = match hint {
+            Value::String(hint) => hint.clone(),
+            _ => {
+                self.errors.push(HintError::NonStringHint {
+                    schema_path,
+                    hint: hint.clone(),
+                });
+
+                return;
+            }
+        };
+
+        match schema {
+            Schema::Type {
+                type_: Type::String,
+                ..
+            } => {
+                if self.config.hints.resolve(&hint).is_none() {
+                    self.errors
+                        .push(HintError::UnknownHint { schema_path, hint });
+                }
+            }
+            _ => self
+                .errors
+                .push(HintError::MisplacedHint { schema_path, hint }),
+        }
+    }
+
+    fn lint_pattern(&mut self, schema: &Schema, pattern: &Value) {
+        let schema_path = self.schema_path.clone();
+
+        let pattern = match pattern {
+            Value::String(pattern) => pattern.clone(),
+            _ => {
+                self.errors.push(HintError::InvalidPattern {
+                    schema_path,
+                    pattern: pattern.to_string(),
+                    reason: "not a string".to_owned(),
+                });
+
+                return;
+            }
+        };
+
+        match schema {
+            Schema::Type {
+                type_: Type::String,
+                ..
+            } => {
+                if let Err(reason) = Pattern::parse(&pattern, 0) {
+                    self.errors.push(HintError::InvalidPattern {
+                        schema_path,
+                        pattern,
+                        reason,
+                    });
+                }
+            }
+            _ => self.errors.push(HintError::MisplacedPattern {
+                schema_path,
+                pattern,
+            }),
+        }
+    }
+
+    fn lint_at(&mut self, tokens: &[&str], schema: &Schema) {
+        let len = self.schema_path.len();
+        self.schema_path
+            .extend(tokens.iter().map(|token| token.to_string()));
+        self.lint(schema);
+        self.schema_path.truncate(len);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_lint_hints() {
+        let schema = json!({
+            "definitions": {
+                "unused": {
+                    "type": "string",
+                    "metadata": { "fuzzHint": "acme/order_id" },
+                },
+            },
+            "metadata": { "fuzzHint": "en_us/internet/email" },
+            "properties": {
+                "email": {
+                    "type": "string",
+                    "nullable": true,
+                    "metadata": { "fuzzHint": "en_us/internet/email" },
+                },
+                "typo": {
+                    "type": "string",
+                    "metadata": { "fuzzHint": "en_us/internet/emial" },
+                },
+            },
+            "optionalProperties": {
+                "count": {
+                    "type": "uint8",
+                    "metadata": { "fuzzHint": "lorem/word" },
+                },
+                "tags": {
+                    "elements": {
+                        "type": "string",
+                        "metadata": { "fuzzHint": ["lorem/word"] },
+                    },
+                },
+            },
+        });
+        let schema = Schema::from_serde_schema(serde_json::from_value(schema).unwrap()).unwrap();
+
+        assert_eq!(
+            lint_hints(&schema),
+            vec![
+                HintError::MisplacedHint {
+                    schema_path: vec![],
+                    hint: "en_us/internet/email".into(),
+                },
+                HintError::UnknownHint {
+                    schema_path: vec!["properties".into(), "typo".into()],
+                    hint: "en_us/internet/emial".into(),
+                },
+                HintError::MisplacedHint {
+                    schema_path: vec!["optionalProperties".into(), "count".into()],
+                    hint: "lorem/word".into(),
+                },
+                HintError::NonStringHint {
+                    schema_path: vec![
+                        "optionalProperties".into(),
+                        "tags".into(),
+                        "elements".into(),
+                    ],
+                    hint: json!(["lorem/word"]),
+                },
+                HintError::UnknownHint {
+                    schema_path: vec!["definitions".into(), "unused".into()],
+                    hint: "acme/order_id".into(),
+                },
+            ],
+        );
+
+        // Registered hints are known.
+        let config = FuzzConfig::new().with_hint("acme/order_id", |_: &mut dyn rand::RngCore| {
+            "ORD-00000000".to_owned()
+        });
+        assert_eq!(lint_hints_with_config(&schema, &config).len(), 4);
+    }
+
+    #[test]
+    fn test_lint_patterns() {
+        let schema = json!({
+            "properties": {
+                "id": {
+                    "type": "string",
+                    "metadata": { "fuzzPattern": "^ORD-[0-9]{8}$" },
+                },
+                "unclosed": {
+                    "type": "string",
+                    "metadata": { "fuzzPattern": "[a-" },
+                },
+                "number": {
+                    "type": "string",
+                    "metadata": { "fuzzPattern": 123 },
+                },
+                "count": {
+                    "type": "uint32",
+                    "metadata": { "fuzzPattern": "[0-9]+" },
+                },
+                "huge": {
+                    "type": "string",
+                    "metadata": { "fuzzPattern": "^(a{1000}){1000}{1000}$" },
+                },
+            },
+        });
+        let schema = Schema::from_serde_schema(serde_json::from_value(schema).unwrap()).unwrap();
+
+        assert_eq!(
+            lint_hints(&schema),
+            vec![
+                HintError::MisplacedPattern {
+                    schema_path: vec!["properties".into(), "count".into()],
+                    pattern: "[0-9]+".into(),
+                },
+                HintError::InvalidPattern {
+                    schema_path: vec!["properties".into(), "huge".into()],
+                    pattern: "^(a{1000}){1000}{1000}$".into(),
+                    reason: "pattern generates strings of more than 10000 characters".into(),
+                },
+                HintError::InvalidPattern {
+                    schema_path: vec!["properties".into(), "number".into()],
+                    pattern: "123".into(),
+                    reason: "not a string".into(),
+                },
+                HintError::InvalidPattern {
+                    schema_path: vec!["properties".into(), "unclosed".into()],
+                    pattern: "[a-".into(),
+                    reason: "unclosed character class".into(),
+                },
+            ],
+        );
+    }
+}
@@ -1,11 +1,20 @@
-use anyhow::{Context, Result};
-use clap::{load_yaml, crate_version, App, AppSettings};
+use anyhow::{bail, Context, Result};
+use chrono::{DateTime, Datelike, FixedOffset, TimeZone, Utc};
+use clap::{crate_version, load_yaml, App, AppSettings, ArgMatches};
 use jtd::Schema;
-use rand::SeedableRng;
-use rand_pcg::Pcg32;
+#[cfg(feature = "cbor")]
+use jtd_fuzz::CborTimestamps;
+use jtd_fuzz::{CharClass, FloatDistribution, FuzzConfig, HintRegistry, NumberStrategy};
+use serde_json::{json, Value};
 
-use std::fs::File;
-use std::io::{stdin, BufReader, Read};
+#[cfg(any(feature = "cbor", feature = "msgpack"))]
+use std::convert::TryFrom;
+use std::fs::{self, File};
+use std::io::{self, stdin, stdout, BufRead, BufReader, BufWriter, Read, Write};
+use std::ops::{Range, RangeInclusive};
+use std::path::Path;
+use std::str::FromStr;
+use std::thread;
 
 fn main() -> Result<()> {
     let cli_yaml = load_yaml!("cli.yaml");
@@ -14,16 +23,29 @@ fn main() -> Result<()> {
         .version(crate_version!())
         .get_matches();
 
+    if let Some(matches) = matches.subcommand_matches("encode") {
+        return encode(matches);
+    }
+
+    if matches.is_present("list-hints") {
+        let mut out = stdout();
+        for name in HintRegistry::new().names() {
+            writeln!(out, "{}", name)?;
+        }
+
+        return Ok(());
+    }
+
     // Parse num-values and seed first, so that we can give the user an error
     // before potentially blocking as we read in the schema.
-
-    let mut rng = if let Some(seed) = matches.value_of("seed") {
-        Pcg32::seed_from_u64(
-            seed.parse()
-                .with_context(|| format!("Failed to parse seed: {}", seed))?,
-        )
+    //
+    // Each value is generated from its own RNG, derived from the seed and the
+    // value's index, so that any one value can be regenerated with --index.
+    let seed: u64 = if let Some(seed) = matches.value_of("seed") {
+        seed.parse()
+            .with_context(|| format!("Failed to parse seed: {}", seed))?
     } else {
-        Pcg32::from_entropy()
+        rand::random()
     };
 
     let num_values: Option<u64> = if let Some(n) = matches.value_of("num-values") {
@@ -35,27 +57,754 @@ fn main() -> Result<()> {
         None
     };
 
-    let reader = BufReader::new(match matches.value_of("input").unwrap() {
-        "-" => Box::new(stdin()) as Box<dyn Read>,
-        file @ _ => Box::new(File::open(file)?) as Box<dyn Read>,
+    let index: Option<u64> = if let Some(i) = matches.value_of("index") {
+        Some(
+            i.parse()
+                .with_context(|| format!("Failed to parse index: {}", i))?,
+        )
+    } else {
+        None
+    };
+
+    let jobs: usize = parse_arg(&matches, "jobs")?;
+    if jobs == 0 {
+        bail!("jobs must be at least 1");
+    }
+
+    let all_definitions = matches.is_present("all-definitions");
+    let print_index = matches.is_present("print-index");
+
+    let format = matches.value_of("format").unwrap();
+    let binary = format == "cbor" || format == "msgpack";
+    if (format == "cbor" && !cfg!(feature = "cbor"))
+        || (format == "msgpack" && !cfg!(feature = "msgpack"))
+    {
+        bail!(
+            "--format {} requires jtd-fuzz to be built with the {} feature",
+            format,
+            format
+        );
+    }
+
+    if format == "array" && num_values.is_none() && index.is_none() {
+        bail!("--format array requires -n or --index");
+    }
+
+    if all_definitions && (format == "array" || binary) {
+        bail!("--all-definitions can only be used with --format ndjson, pretty, or envelope");
+    }
+
+    // Concatenating the arrays of several shards wouldn't give one array.
+    if matches.is_present("shard") && format == "array" {
+        bail!("--shard cannot be used with --format array");
+    }
+
+    if print_index && format != "ndjson" && format != "pretty" {
+        bail!("--print-index can only be used with --format ndjson or pretty");
+    }
+
+    // The indices of the values to generate. Without -n, values are generated
+    // until the output is closed, or just one value per definition with
+    // --all-definitions.
+    let indices = if let Some(i) = index {
+        i..i + 1
+    } else if let Some(shard) = matches.value_of("shard") {
+        shard_indices(shard, num_values.unwrap())?
+    } else if all_definitions {
+        0..num_values.unwrap_or(1)
+    } else {
+        0..num_values.unwrap_or(u64::MAX)
+    };
+
+    let config = parse_config(&matches)?;
+    let schema = read_schema(matches.value_of("input").unwrap())?;
+
+    // Hints and patterns that would be ignored are most likely typos, so
+    // point them out.
+    let hint_errors = jtd_fuzz::lint_hints_with_config(&schema, &config);
+    let strict_hints = matches.is_present("strict-hints");
+    for err in &hint_errors {
+        eprintln!(
+            "{}: {}",
+            if strict_hints { "error" } else { "warning" },
+            err
+        );
+    }
+
+    if strict_hints && !hint_errors.is_empty() {
+        bail!("Schema has fuzzHints or fuzzPatterns that would be ignored");
+    }
+
+    // The schemas to generate values from, each with the label to print before
+    // its values, if any.
+    let roots = if all_definitions {
+        schema
+            .definitions()
+            .keys()
+            .map(|name| (Some(name.as_str()), definition_root(&schema, name)))
+            .collect()
+    } else if let Some(name) = matches.value_of("definition") {
+        if !schema.definitions().contains_key(name) {
+            bail!("No such definition: {}", name);
+        }
+
+        vec![(None, definition_root(&schema, name))]
+    } else {
+        vec![(None, schema)]
+    };
+
+    let output = Output {
+        format,
+        seed,
+        config: &config,
+        invalid: matches.is_present("invalid"),
+        print_index,
+        #[cfg(feature = "cbor")]
+        cbor_timestamps: match matches.value_of("cbor-timestamps").unwrap() {
+            "tag0" => CborTimestamps::DateTime,
+            "tag1" => CborTimestamps::Epoch,
+            _ => CborTimestamps::Text,
+        },
+    };
+
+    let mut out: BufWriter<Box<dyn Write>> = BufWriter::new(match matches.value_of("output") {
+        Some(path) if path != "-" => {
+            Box::new(File::create(path).with_context(|| format!("Failed to create {}", path))?)
+        }
+        _ => Box::new(stdout()),
     });
 
+    match output.write_roots(&mut out, jobs, indices, &roots) {
+        // Stop quietly when the output is closed, such as when piping into
+        // head.
+        Err(err)
+            if err
+                .downcast_ref::<io::Error>()
+                .is_some_and(|err| err.kind() == io::ErrorKind::BrokenPipe) =>
+        {
+            Ok(())
+        }
+        result => result,
+    }
+}
+
+// How values are generated and written out.
+struct Output<'a> {
+    format: &'a str,
+    seed: u64,
+    config: &'a FuzzConfig,
+    invalid: bool,
+    print_index: bool,
+    #[cfg(feature = "cbor")]
+    cbor_timestamps: CborTimestamps,
+}
+
+impl Output<'_> {
+    // Writes the values at the given indices for each of the roots in turn,
+    // wrapped in whatever the format needs around them.
+    fn write_roots(
+        &self,
+        out: &mut impl Write,
+        jobs: usize,
+        indices: Range<u64>,
+        roots: &[(Option<&str>, Schema)],
+    ) -> Result<()> {
+        if self.format == "array" {
+            write!(out, "[")?;
+        }
+
+        for (label, schema) in roots {
+            write_values(out, jobs, indices.clone(), &|index, out| {
+                self.write_value(*label, schema, index, index == indices.start, out)
+            })?;
+        }
+
+        if self.format == "array" {
+            writeln!(out, "\n]")?;
+        }
+
+        Ok(out.flush()?)
+    }
+
+    // Generates the value at an index, and writes it out. first says whether
+    // it's the first value of the output, which arrays don't put a comma
+    // before.
+    fn write_value(
+        &self,
+        label: Option<&str>,
+        schema: &Schema,
+        index: u64,
+        first: bool,
+        out: &mut dyn Write,
+    ) -> Result<()> {
+        let config = self.config;
+        let mut rng = jtd_fuzz::nth_rng(self.seed, index);
+
+        // With --invalid, the value written out is an object describing the
+        // invalid instance.
+        let invalid = if self.invalid {
+            let invalid = jtd_fuzz::fuzz_invalid_with_config(schema, config, &mut rng)
+                .with_context(|| "Schema accepts all data, so no invalid data can be generated")?;
+
+            Some(json!({
+                "instance": invalid.instance,
+                "instancePath": json_pointer(&invalid.instance_path),
+                "schemaPath": json_pointer(&invalid.schema_path),
+            }))
+        } else {
+            None
+        };
+
+        #[cfg(any(feature = "cbor", feature = "msgpack"))]
+        if self.format == "cbor" || self.format == "msgpack" {
+            // Binary values aren't self-delimiting in general, so each is
+            // preceded by its length in bytes, as a big-endian u32.
+            let mut item = Vec::new();
+            match (self.format, &invalid) {
+                #[cfg(feature = "cbor")]
+                ("cbor", Some(value)) => ciborium::into_writer(value, &mut item)?,
+                #[cfg(feature = "cbor")]
+                ("cbor", None) => jtd_fuzz::fuzz_to_cbor_with_config(
+                    schema,
+                    config,
+                    self.cbor_timestamps,
+                    &mut rng,
+                    &mut item,
+                )?,
+                #[cfg(feature = "msgpack")]
+                ("msgpack", Some(value)) => rmp_serde::encode::write(&mut item, value)?,
+                #[cfg(feature = "msgpack")]
+                ("msgpack", None) => {
+                    jtd_fuzz::fuzz_to_msgpack_with_config(schema, config, &mut rng, &mut item)?
+                }
+                _ => unreachable!("binary formats are only enabled with their features"),
+            }
+
+            let len = u32::try_from(item.len())
+                .with_context(|| format!("Value is too large to output: {} bytes", item.len()))?;
+            out.write_all(&len.to_be_bytes())?;
+            out.write_all(&item)?;
+            return Ok(());
+        }
+
+        match self.format {
+            "array" => write!(out, "{}", if first { "\n" } else { ",\n" })?,
+            "envelope" => {
+                write!(out, "{{")?;
+                if let Some(label) = label {
+                    write!(out, "\"definition\":{},", Value::from(label))?;
+                }
+
+                write!(out, "\"index\":{},\"seed\":{},\"value\":", index, self.seed)?;
+            }
+            _ => {
+                if let Some(label) = label {
+                    write!(out, "{}\t", label)?;
+                }
+
+                if self.print_index {
+                    write!(out, "{}\t", index)?;
+                }
+            }
+        }
+
+        let pretty = self.format == "pretty";
+        match invalid {
+            Some(value) if pretty => serde_json::to_writer_pretty(&mut *out, &value)?,
+            Some(value) => serde_json::to_writer(&mut *out, &value)?,
+            None if pretty => {
+                // Write values out as they're generated, so that large values
+                // don't have to fit in memory.
+                let serializer = &mut serde_json::Serializer::pretty(&mut *out);
+                jtd_fuzz::fuzz_to_serializer_with_config(schema, config, &mut rng, serializer)
+                    .map_err(io::Error::from)?;
+            }
+            None => jtd_fuzz::fuzz_to_writer_with_config(schema, config, &mut rng, &mut *out)
+                .map_err(io::Error::from)?,
+        }
+
+        match self.format {
+            "array" => {}
+            "envelope" => writeln!(out, "}}")?,
+            _ => writeln!(out)?,
+        }
+
+        Ok(())
+    }
+}
+
+// Returns a schema whose root is a ref to one of schema's definitions. Values
+// generated from it are the same as jtd_fuzz::fuzz_definition generates.
+fn definition_root(schema: &Schema, name: &str) -> Schema {
+    Schema::Ref {
+        definitions: schema.definitions().clone(),
+        metadata: Default::default(),
+        nullable: false,
+        ref_: name.to_owned(),
+    }
+}
+
+// How many values each job generates at a time.
+const BATCH_SIZE: u64 = 1024;
+
+// Writes the values at the given indices, in order. With more than one job,
+// values are generated in batches spread across that many threads.
+fn write_values(
+    out: &mut impl Write,
+    jobs: usize,
+    indices: Range<u64>,
+    generate: &(dyn Fn(u64, &mut dyn Write) -> Result<()> + Sync),
+) -> Result<()> {
+    if jobs == 1 {
+        for i in indices {
+            generate(i, out)?;
+        }
+
+        return Ok(out.flush()?);
+    }
+
+    let mut start = indices.start;
+    while start < indices.end {
+        let end = indices
+            .end
+            .min(start.saturating_add(BATCH_SIZE * jobs as u64));
+
+        let batches: Vec<Result<Vec<u8>>> = thread::scope(|scope| {
+            let handles: Vec<_> = job_indices(start..end, jobs)
+                .into_iter()
+                .map(|indices| {
+                    scope.spawn(move || {
+                        let mut batch = Vec::new();
+                        for i in indices {
+                            generate(i, &mut batch)?;
+                        }
+
+                        Ok(batch)
+                    })
+                })
+                .collect();
+
+            handles
+                .into_iter()
+                .map(|handle| handle.join().unwrap())
+                .collect()
+        });
+
+        for batch in batches {
+            out.write_all(&batch?)?;
+        }
+
+        start = end;
+    }
+
+    Ok(out.flush()?)
+}
+
+// Splits the indices of a batch into contiguous runs, one for each job, in
+// order. When the indices don't divide evenly, the last runs are shorter, and
+// may be empty.
+fn job_indices(batch: Range<u64>, jobs: usize) -> Vec<Range<u64>> {
+    let per_job = (batch.end - batch.start).div_ceil(jobs as u64);
+
+    (0..jobs as u64)
+        .map(|job| {
+            let low = batch.end.min(batch.start + job * per_job);
+            let high = batch.end.min(low + per_job);
+            low..high
+        })
+        .collect()
+}
+
+// Parses a shard, like "2/5", into the indices that shard generates. Shards
+// split the num_values indices into contiguous runs, so that the output of
+// shards 1 through n, in order, is the same as that of the whole run.
+fn shard_indices(shard: &str, num_values: u64) -> Result<Range<u64>> {
+    let (i, n) = match shard.split_once('/') {
+        Some((i, n)) => (
+            i.parse::<u64>()
+                .with_context(|| format!("Failed to parse shard: {}", shard))?,
+            n.parse::<u64>()
+                .with_context(|| format!("Failed to parse shard: {}", shard))?,
+        ),
+        None => bail!("shard must be of the form i/n: {}", shard),
+    };
+
+    if n == 0 || i < 1 || i > n {
+        bail!("shard must be between 1/{} and {}/{}: {}", n, n, n, shard);
+    }
+
+    let bound = |i: u64| (num_values as u128 * i as u128 / n as u128) as u64;
+    Ok(bound(i - 1)..bound(i))
+}
+
+fn encode(matches: &ArgMatches) -> Result<()> {
+    let config = parse_config(matches)?;
+    let schema = read_schema(matches.value_of("schema").unwrap())?;
+
+    let output_dir = Path::new(matches.value_of("output-dir").unwrap());
+    fs::create_dir_all(output_dir).with_context(|| {
+        format!(
+            "Failed to create output directory: {}",
+            output_dir.display()
+        )
+    })?;
+
+    // Instances that can't be encoded are skipped rather than treated as
+    // fatal, since real-world data often has a few values jtd-fuzz would
+    // never generate.
+    for (i, line) in open(matches.value_of("instances").unwrap())?
+        .lines()
+        .enumerate()
+    {
+        let line_number = i + 1;
+        let line = line.with_context(|| "Failed to read instances")?;
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let instance: Value = serde_json::from_str(&line)
+            .with_context(|| format!("Failed to parse instance on line {}", line_number))?;
+
+        match jtd_fuzz::encode_with_config(&schema, &config, &instance) {
+            Ok(bytes) => {
+                let path = output_dir.join(format!("{:08}", line_number));
+                fs::write(&path, bytes)
+                    .with_context(|| format!("Failed to write {}", path.display()))?;
+            }
+            Err(err) => eprintln!("Skipping instance on line {}: {}", line_number, err),
+        }
+    }
+
+    Ok(())
+}
+
+fn parse_config(matches: &ArgMatches) -> Result<FuzzConfig> {
+    Ok(FuzzConfig::new()
+        .with_string_length(parse_length(matches, "string-length")?)
+        .with_char_classes(
+            matches
+                .values_of("chars")
+                .unwrap()
+                .map(parse_char_class)
+                .collect(),
+        )
+        .with_elements_length(parse_length(matches, "elements")?)
+        .with_values_length(parse_length(matches, "values")?)
+        .with_additional_properties_length(parse_length(matches, "additional-properties")?)
+        .with_null_probability(parse_probability(matches, "null-probability")?)
+        .with_optional_property_probability(parse_probability(
+            matches,
+            "optional-property-probability",
+        )?)
+        .with_max_recursion_depth(parse_arg(matches, "max-recursion-depth")?)
+        .with_number_strategy(match matches.value_of("numbers").unwrap() {
+            "edges" => NumberStrategy::Edges,
+            _ => NumberStrategy::Uniform,
+        })
+        .with_float_distribution(match matches.value_of("floats").unwrap() {
+            "log-uniform" => FloatDistribution::LogUniform,
+            "bits" => FloatDistribution::Bits,
+            _ => FloatDistribution::Unit,
+        })
+        .with_timestamp_range(parse_timestamp_range(matches)?)
+        .with_utc_timestamps(matches.is_present("utc-timestamps"))
+        .with_timestamp_precision(parse_timestamp_precision(matches)?)
+        .with_timestamp_edges(matches.is_present("timestamp-edges")))
+}
+
+fn open(path: &str) -> Result<BufReader<Box<dyn Read>>> {
+    Ok(BufReader::new(match path {
+        "-" => Box::new(stdin()) as Box<dyn Read>,
+        file => Box::new(File::open(file).with_context(|| format!("Failed to open {}", file))?)
+            as Box<dyn Read>,
+    }))
+}
+
+fn read_schema(path: &str) -> Result<Schema> {
     let schema = Schema::from_serde_schema(
-        serde_json::from_reader(reader).with_context(|| "Failed to parse schema")?,
+        serde_json::from_reader(open(path)?).with_context(|| "Failed to parse schema")?,
     )
     .with_context(|| "Malformed schema")?;
 
     schema.validate().with_context(|| "Invalid schema")?;
+    jtd_fuzz::min_depths(&schema).with_context(|| "Unsatisfiable schema")?;
 
-    if let Some(n) = num_values {
-        for _ in 0..n {
-            println!("{}", jtd_fuzz::fuzz(&schema, &mut rng));
-        }
-    } else {
-        loop {
-            println!("{}", jtd_fuzz::fuzz(&schema, &mut rng));
+    Ok(schema)
+}
+
+fn parse_arg<T>(matches: &ArgMatches, name: &str) -> Result<T>
+where
+    T: FromStr,
+    T::Err: std::error::Error + Send + Sync + 'static,
+{
+    let value = matches.value_of(name).unwrap();
+    value
+        .parse()
+        .with_context(|| format!("Failed to parse {}: {}", name, value))
+}
+
+fn parse_length(matches: &ArgMatches, name: &str) -> Result<RangeInclusive<usize>> {
+    let min = parse_arg(matches, &format!("min-{}", name))?;
+    let max = parse_arg(matches, &format!("max-{}", name))?;
+
+    if min > max {
+        bail!(
+            "min-{} ({}) is greater than max-{} ({})",
+            name,
+            min,
+            name,
+            max
+        );
+    }
+
+    Ok(min..=max)
+}
+
+fn parse_probability(matches: &ArgMatches, name: &str) -> Result<f64> {
+    let p = parse_arg(matches, name)?;
+
+    if !(0.0..=1.0).contains(&p) {
+        bail!("{} must be between 0 and 1: {}", name, p);
+    }
+
+    Ok(p)
+}
+
+fn parse_timestamp_range(matches: &ArgMatches) -> Result<RangeInclusive<DateTime<Utc>>> {
+    let min = parse_arg::<DateTime<FixedOffset>>(matches, "timestamp-min")?.with_timezone(&Utc);
+    let max = parse_arg::<DateTime<FixedOffset>>(matches, "timestamp-max")?.with_timezone(&Utc);
+
+    if min > max {
+        bail!(
+            "timestamp-min ({}) is later than timestamp-max ({})",
+            min.to_rfc3339(),
+            max.to_rfc3339()
+        );
+    }
+
+    // RFC3339 timestamps can represent instants outside of this range using
+    // offsets, but those instants can't be written in UTC.
+    if min < Utc.ymd(1, 1, 1).and_hms(0, 0, 0) || max.year() > 9999 {
+        bail!("timestamp range must be between years 0001 and 9999 in UTC");
+    }
+
+    Ok(min..=max)
+}
+
+fn parse_timestamp_precision(matches: &ArgMatches) -> Result<usize> {
+    let precision = parse_arg(matches, "timestamp-precision")?;
+
+    if precision > 9 {
+        bail!("timestamp-precision must be at most 9: {}", precision);
+    }
+
+    Ok(precision)
+}
+
+fn parse_char_class(class: &str) -> CharClass {
+    match class {
+        "bmp" => CharClass::Bmp,
+        "astral" => CharClass::Astral,
+        "combining" => CharClass::Combining,
+        "rtl" => CharClass::Rtl,
+        "zero-width" => CharClass::ZeroWidth,
+        "control" => CharClass::Control,
+        "normalization" => CharClass::Normalization,
+        _ => CharClass::PrintableAscii,
+    }
+}
+
+fn json_pointer(tokens: &[String]) -> String {
+    tokens
+        .iter()
+        .map(|token| format!("/{}", token.replace('~', "~0").replace('/', "~1")))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A schema whose values are always {"x":"a"}, and whose one definition's
+    // values are always "b", so that output can be checked byte for byte.
+    fn constant_schema() -> Schema {
+        Schema::from_serde_schema(
+            serde_json::from_value(json!({
+                "definitions": {
+                    "d": { "enum": ["b"] },
+                },
+                "properties": {
+                    "x": { "enum": ["a"] },
+                },
+            }))
+            .unwrap(),
+        )
+        .unwrap()
+    }
+
+    fn write_output(
+        format: &str,
+        print_index: bool,
+        indices: Range<u64>,
+        roots: &[(Option<&str>, Schema)],
+    ) -> Vec<u8> {
+        let config = FuzzConfig::new();
+        let output = Output {
+            format,
+            seed: 8927,
+            config: &config,
+            invalid: false,
+            print_index,
+            #[cfg(feature = "cbor")]
+            cbor_timestamps: CborTimestamps::Text,
+        };
+
+        let mut out = Vec::new();
+        output.write_roots(&mut out, 2, indices, roots).unwrap();
+        out
+    }
+
+    #[test]
+    fn test_write_json() {
+        let schema = constant_schema();
+        let root = [(None, schema.clone())];
+
+        assert_eq!(
+            "{\"x\":\"a\"}\n{\"x\":\"a\"}\n",
+            String::from_utf8(write_output("ndjson", false, 0..2, &root)).unwrap(),
+        );
+
+        assert_eq!(
+            "5\t{\"x\":\"a\"}\n6\t{\"x\":\"a\"}\n",
+            String::from_utf8(write_output("ndjson", true, 5..7, &root)).unwrap(),
+        );
+
+        assert_eq!(
+            "{\n  \"x\": \"a\"\n}\n",
+            String::from_utf8(write_output("pretty", false, 0..1, &root)).unwrap(),
+        );
+
+        // There's no comma before the first value, even when it isn't at
+        // index 0, as with --index or --shard.
+        assert_eq!(
+            "[\n{\"x\":\"a\"}\n]\n",
+            String::from_utf8(write_output("array", false, 7..8, &root)).unwrap(),
+        );
+        assert_eq!(
+            "[\n{\"x\":\"a\"},\n{\"x\":\"a\"},\n{\"x\":\"a\"}\n]\n",
+            String::from_utf8(write_output("array", false, 3..6, &root)).unwrap(),
+        );
+
+        assert_eq!(
+            "{\"index\":3,\"seed\":8927,\"value\":{\"x\":\"a\"}}\n",
+            String::from_utf8(write_output("envelope", false, 3..4, &root)).unwrap(),
+        );
+    }
+
+    #[test]
+    fn test_write_labeled_json() {
+        let schema = constant_schema();
+        let roots = [
+            (Some("d"), definition_root(&schema, "d")),
+            (Some("e \"f\""), schema),
+        ];
+
+        assert_eq!(
+            "d\t0\t\"b\"\nd\t1\t\"b\"\ne \"f\"\t0\t{\"x\":\"a\"}\ne \"f\"\t1\t{\"x\":\"a\"}\n",
+            String::from_utf8(write_output("ndjson", true, 0..2, &roots)).unwrap(),
+        );
+
+        assert_eq!(
+            concat!(
+                "{\"definition\":\"d\",\"index\":0,\"seed\":8927,\"value\":\"b\"}\n",
+                "{\"definition\":\"e \\\"f\\\"\",\"index\":0,\"seed\":8927,\"value\":{\"x\":\"a\"}}\n",
+            ),
+            String::from_utf8(write_output("envelope", false, 0..1, &roots)).unwrap(),
+        );
+    }
+
+    #[cfg(feature = "cbor")]
+    #[test]
+    fn test_write_cbor() {
+        let root = [(None, constant_schema())];
+
+        // A map of one pair, from the text "x" to the text "a", preceded by
+        // its length.
+        let value = [0, 0, 0, 5, 0xa1, 0x61, b'x', 0x61, b'a'];
+        assert_eq!(
+            [value, value].concat(),
+            write_output("cbor", false, 0..2, &root)
+        );
+    }
+
+    #[cfg(feature = "msgpack")]
+    #[test]
+    fn test_write_msgpack() {
+        let root = [(None, constant_schema())];
+
+        // A map of one pair, from the string "x" to the string "a", preceded
+        // by its length.
+        let value = [0, 0, 0, 5, 0x81, 0xa1, b'x', 0xa1, b'a'];
+        assert_eq!(
+            [value, value].concat(),
+            write_output("msgpack", false, 0..2, &root)
+        );
+    }
+
+    #[test]
+    fn test_job_indices() {
+        assert_eq!(vec![0..4, 4..8], job_indices(0..8, 2));
+
+        // The last job picks up whatever is left over.
+        assert_eq!(vec![10..14, 14..18, 18..20], job_indices(10..20, 3));
+
+        // Jobs with nothing left over get nothing.
+        assert_eq!(vec![0..1, 1..2, 2..2, 2..2], job_indices(0..2, 4));
+        assert_eq!(vec![5..5, 5..5], job_indices(5..5, 2));
+    }
+
+    #[test]
+    fn test_write_values() {
+        let generate = |i: u64, out: &mut dyn Write| -> Result<()> {
+            writeln!(out, "{}", i)?;
+            Ok(())
+        };
+
+        // However many jobs there are, values come out in order, including
+        // across batches.
+        let indices = 3..BATCH_SIZE * 5 + 2;
+        let mut expected = Vec::new();
+        write_values(&mut expected, 1, indices.clone(), &generate).unwrap();
+
+        for jobs in 2..=6 {
+            let mut out = Vec::new();
+            write_values(&mut out, jobs, indices.clone(), &generate).unwrap();
+            assert_eq!(expected, out, "jobs = {}", jobs);
         }
     }
 
-    Ok(())
+    #[test]
+    fn test_shard_indices() {
+        // Uneven shards are still contiguous, and cover every index.
+        let shards: Vec<_> = (1..=3)
+            .map(|i| shard_indices(&format!("{}/3", i), 10).unwrap())
+            .collect();
+        assert_eq!(vec![0..3, 3..6, 6..10], shards);
+
+        // With fewer values than shards, some shards are empty.
+        let shards: Vec<_> = (1..=5)
+            .map(|i| shard_indices(&format!("{}/5", i), 2).unwrap())
+            .collect();
+        assert_eq!(vec![0..0, 0..0, 0..1, 1..1, 1..2], shards);
+
+        // Shards don't overflow, even with the most values there can be.
+        assert_eq!(
+            u64::MAX / 2..u64::MAX,
+            shard_indices("2/2", u64::MAX).unwrap()
+        );
+
+        for shard in &["0/3", "4/3", "1/0", "1", "a/3", "1/b"] {
+            assert!(shard_indices(shard, 10).is_err(), "{}", shard);
+        }
+    }
 }
@@ -0,0 +1,379 @@
+use crate::encode::Encoder;
+use crate::FuzzConfig;
+use rand::distributions::{Distribution, Standard};
+use rand::seq::IteratorRandom;
+use std::convert::TryFrom;
+use std::ops::Range;
+
+/// How numbers are generated.
+///
+/// See [`FuzzConfig::with_number_strategy`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum NumberStrategy {
+    /// Generate numbers without any special preference for particular values.
+    Uniform,
+
+    /// Generate "edge case" numbers half the time.
+    ///
+    /// For integers, the edge cases are zero, one, negative one, and the
+    /// minimum and maximum values of each integer type (as well as the numbers
+    /// right next to them) that fit in the type being generated. For instance,
+    /// a `uint8` may be `127` or `128`, the edges of `int8`.
+    ///
+    /// For floats, the edge cases are zero, negative zero, one, negative one,
+    /// the smallest and largest finite values, the smallest normal and
+    /// subnormal values, epsilon, the largest integers that can be represented
+    /// exactly, random integral values, and random subnormal values.
+    Edges,
+}
+
+/// How floats are generated, apart from the edge cases favored by
+/// [`NumberStrategy::Edges`].
+///
+/// See [`FuzzConfig::with_float_distribution`].
+///
+/// Regardless of distribution, generated floats are always finite. Values for
+/// `float32` schemas are always exactly representable as a 32-bit float.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FloatDistribution {
+    /// Generate floats uniformly between zero (inclusive) and one (exclusive).
+    Unit,
+
+    /// Generate floats of either sign, where the base-2 logarithm of the
+    /// magnitude is uniformly distributed across all finite values.
+    ///
+    /// Tiny, subnormal values like `1e-310` are as likely as huge ones like
+    /// `1e300`, or everyday ones like `42.5`.
+    LogUniform,
+
+    /// Generate floats from random bit patterns, skipping the ones for
+    /// infinity and NaN.
+    Bits,
+}
+
+// The integers that NumberStrategy::Edges favors, for all integer types. Only
+// the ones that fit in the type being generated are used.
+const INT_EDGES: &[i64] = &[
+    i32::MIN as i64,
+    i32::MIN as i64 + 1,
+    i16::MIN as i64,
+    i16::MIN as i64 + 1,
+    i8::MIN as i64,
+    i8::MIN as i64 + 1,
+    -1,
+    0,
+    1,
+    i8::MAX as i64 - 1,
+    i8::MAX as i64,
+    i8::MAX as i64 + 1,
+    u8::MAX as i64 - 1,
+    u8::MAX as i64,
+    i16::MAX as i64 - 1,
+    i16::MAX as i64,
+    i16::MAX as i64 + 1,
+    u16::MAX as i64 - 1,
+    u16::MAX as i64,
+    i32::MAX as i64 - 1,
+    i32::MAX as i64,
+    i32::MAX as i64 + 1,
+    u32::MAX as i64 - 1,
+    u32::MAX as i64,
+];
+
+const F32_EDGES: &[f32] = &[
+    0.0,
+    -0.0,
+    1.0,
+    -1.0,
+    f32::EPSILON,
+    f32::MIN_POSITIVE,
+    -f32::MIN_POSITIVE,
+    f32::MAX,
+    f32::MIN,
+    // The largest integer below which all integers are exactly representable.
+    16_777_216.0,
+    -16_777_216.0,
+];
+
+const F64_EDGES: &[f64] = &[
+    0.0,
+    -0.0,
+    1.0,
+    -1.0,
+    f64::EPSILON,
+    f64::MIN_POSITIVE,
+    -f64::MIN_POSITIVE,
+    f64::MAX,
+    f64::MIN,
+    // The largest integer below which all integers are exactly representable.
+    9_007_199_254_740_992.0,
+    -9_007_199_254_740_992.0,
+    // The largest values of float32 are worth trying in float64 as well, to
+    // catch consumers that parse into 32-bit floats.
+    f32::MAX as f64,
+    f32::MIN as f64,
+];
+
+pub(crate) fn fuzz_int<T, R>(config: &FuzzConfig, rng: &mut R) -> T
+where
+    T: TryFrom<i64>,
+    Standard: Distribution<T>,
+    R: rand::Rng,
+{
+    if config.number_strategy == NumberStrategy::Edges && rng.gen::<bool>() {
+        return INT_EDGES
+            .iter()
+            .filter_map(|&n| T::try_from(n).ok())
+            .choose(rng)
+            .unwrap();
+    }
+
+    rng.gen()
+}
+
+pub(crate) fn fuzz_float32<R: rand::Rng>(config: &FuzzConfig, rng: &mut R) -> f32 {
+    if config.number_strategy == NumberStrategy::Edges && rng.gen::<bool>() {
+        return match rng.gen_range(0..3) {
+            0 => *F32_EDGES.iter().choose(rng).unwrap(),
+            1 => rng.gen::<i16>() as f32,
+            _ => f32::from_bits(rng.gen_range(1..1 << 23)) * sign(rng) as f32,
+        };
+    }
+
+    match config.float_distribution {
+        FloatDistribution::Unit => rng.gen(),
+        FloatDistribution::LogUniform => {
+            // Keep the mantissa small enough that the result can't round up to
+            // infinity.
+            let (mantissa, exponent) = log_uniform(rng, -149.0..128.0);
+            let mantissa = mantissa.min(2.0 - f32::EPSILON as f64);
+            (mantissa * pow2(exponent)) as f32 * sign(rng) as f32
+        }
+        FloatDistribution::Bits => loop {
+            let n = f32::from_bits(rng.gen());
+            if n.is_finite() {
+                return n;
+            }
+        },
+    }
+}
+
+pub(crate) fn fuzz_float64<R: rand::Rng>(config: &FuzzConfig, rng: &mut R) -> f64 {
+    if config.number_strategy == NumberStrategy::Edges && rng.gen::<bool>() {
+        return match rng.gen_range(0..3) {
+            0 => *F64_EDGES.iter().choose(rng).unwrap(),
+            1 => rng.gen::<i32>() as f64,
+            _ => f64::from_bits(rng.gen_range(1..1 << 52)) * sign(rng),
+        };
+    }
+
+    match config.float_distribution {
+        FloatDistribution::Unit => rng.gen(),
+        FloatDistribution::LogUniform => {
+            // Keep the mantissa small enough that the result can't round up to
+            // infinity.
+            let (mantissa, exponent) = log_uniform(rng, -1074.0..1024.0);
+            let mantissa = mantissa.min(2.0 - f64::EPSILON);
+            mantissa * pow2(exponent) * sign(rng)
+        }
+        FloatDistribution::Bits => loop {
+            let n = f64::from_bits(rng.gen());
+            if n.is_finite() {
+                return n;
+            }
+        },
+    }
+}
+
+// Chooses a number whose base-2 logarithm is uniformly distributed in log2,
+// returned as a mantissa in [1, 2] and a power-of-two exponent.
+fn log_uniform<R: rand::Rng>(rng: &mut R, log2: Range<f64>) -> (f64, i32) {
+    let log2 = rng.gen_range(log2);
+    let exponent = log2.floor();
+    ((log2 - exponent).exp2(), exponent as i32)
+}
+
+// Computes 2^exponent exactly, for any exponent with a finite, nonzero result.
+fn pow2(exponent: i32) -> f64 {
+    if exponent >= -1022 {
+        f64::from_bits(((exponent + 1023) as u64) << 52)
+    } else {
+        // Below -1022, powers of two are subnormal.
+        f64::from_bits(1 << (exponent + 1074))
+    }
+}
+
+fn sign<R: rand::Rng>(rng: &mut R) -> f64 {
+    if rng.gen() {
+        1.0
+    } else {
+        -1.0
+    }
+}
+
+// The inverses of the functions above, for encode(). These always take the
+// ordinary, non-edge path, which can generate any integer, and any float that
+// the float distribution can.
+
+pub(crate) fn encode_int(config: &FuzzConfig, enc: &mut Encoder, n: i64) {
+    if config.number_strategy == NumberStrategy::Edges {
+        enc.bool(false);
+    }
+
+    // Integers of every type are generated from a single word, truncated.
+    enc.u32(n as u32);
+}
+
+pub(crate) fn encode_float32(
+    config: &FuzzConfig,
+    enc: &mut Encoder,
+    n: f64,
+) -> Result<(), &'static str> {
+    if config.number_strategy == NumberStrategy::Edges {
+        enc.bool(false);
+    }
+
+    match config.float_distribution {
+        // rand uses the top 24 bits of a word as a multiple of 2^-24.
+        FloatDistribution::Unit => {
+            let n = unit_multiple(n, 24).ok_or(NOT_UNIT)?;
+            enc.u32((n as u32) << 8);
+        }
+        FloatDistribution::LogUniform => return Err(LOG_UNIFORM),
+        FloatDistribution::Bits => enc.u32((n as f32).to_bits()),
+    }
+
+    Ok(())
+}
+
+pub(crate) fn encode_float64(
+    config: &FuzzConfig,
+    enc: &mut Encoder,
+    n: f64,
+) -> Result<(), &'static str> {
+    if config.number_strategy == NumberStrategy::Edges {
+        enc.bool(false);
+    }
+
+    match config.float_distribution {
+        // rand uses the top 53 bits of a 64-bit word as a multiple of 2^-53.
+        FloatDistribution::Unit => enc.u64(unit_multiple(n, 53).ok_or(NOT_UNIT)? << 11),
+        FloatDistribution::LogUniform => return Err(LOG_UNIFORM),
+        FloatDistribution::Bits => enc.u64(n.to_bits()),
+    }
+
+    Ok(())
+}
+
+pub(crate) const NOT_UNIT: &str = "float is not between zero and one, or has too many digits";
+const LOG_UNIFORM: &str = "log-uniform floats cannot be encoded";
+
+// Returns n / 2^-bits, if n is a multiple of 2^-bits in [0, 1).
+fn unit_multiple(n: f64, bits: i32) -> Option<u64> {
+    let multiple = n * pow2(bits);
+    if (0.0..1.0).contains(&n) && multiple.fract() == 0.0 {
+        Some(multiple as u64)
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use jtd::Schema;
+    use rand::SeedableRng;
+    use serde_json::{json, Value};
+
+    #[test]
+    fn test_edges_int() {
+        let values = fuzz_edges(json!({ "type": "int8" }));
+        for n in &[-128, -127, -1, 0, 1, 126, 127] {
+            assert!(values.contains(&json!(n)), "{}", n);
+        }
+
+        let values = fuzz_edges(json!({ "type": "uint32" }));
+        for n in &[0, 1, 127, 128, 255, 65535, 4294967294_u32, 4294967295_u32] {
+            assert!(values.contains(&json!(n)), "{}", n);
+        }
+    }
+
+    #[test]
+    fn test_edges_float() {
+        let values = fuzz_edges(json!({ "type": "float32" }));
+        for n in &[0.0, 1.0, -1.0, f32::MAX, f32::MIN, f32::MIN_POSITIVE] {
+            assert!(values.contains(&json!(n)), "{}", n);
+        }
+
+        assert!(values.iter().any(is_negative_zero));
+        assert!(values.iter().any(is_subnormal));
+
+        let values = fuzz_edges(json!({ "type": "float64" }));
+        for n in &[0.0, 1.0, -1.0, f64::MAX, f64::MIN, f64::MIN_POSITIVE] {
+            assert!(values.contains(&json!(n)), "{}", n);
+        }
+
+        assert!(values.iter().any(is_negative_zero));
+        assert!(values.iter().any(is_subnormal));
+    }
+
+    #[test]
+    fn test_float_distributions() {
+        for distribution in &[
+            FloatDistribution::Unit,
+            FloatDistribution::LogUniform,
+            FloatDistribution::Bits,
+        ] {
+            let config = FuzzConfig::new().with_float_distribution(*distribution);
+            let mut rng = rand_pcg::Pcg32::seed_from_u64(8927);
+
+            let (mut negative, mut large, mut tiny) = (false, false, false);
+            for _ in 0..1000 {
+                let n = fuzz_float32(&config, &mut rng);
+                assert!(n.is_finite(), "{}", n);
+
+                // float32 values must survive being written out as JSON, read
+                // back in as a float64, and then converted to a float32.
+                let json = serde_json::to_string(&json!(n)).unwrap();
+                let parsed: f64 = serde_json::from_str(&json).unwrap();
+                assert_eq!(n.to_bits(), (parsed as f32).to_bits(), "{}", json);
+
+                let n = fuzz_float64(&config, &mut rng);
+                assert!(n.is_finite(), "{}", n);
+
+                negative |= n < 0.0;
+                large |= n.abs() > 1e100;
+                tiny |= n != 0.0 && n.abs() < 1e-100;
+            }
+
+            let full_range = *distribution != FloatDistribution::Unit;
+            assert_eq!(full_range, negative, "{:?}", distribution);
+            assert_eq!(full_range, large, "{:?}", distribution);
+            assert_eq!(full_range, tiny, "{:?}", distribution);
+        }
+    }
+
+    fn is_negative_zero(value: &Value) -> bool {
+        let n = value.as_f64().unwrap();
+        n == 0.0 && n.is_sign_negative()
+    }
+
+    fn is_subnormal(value: &Value) -> bool {
+        value.as_f64().unwrap().is_subnormal() || (value.as_f64().unwrap() as f32).is_subnormal()
+    }
+
+    fn fuzz_edges(schema: Value) -> Vec<Value> {
+        let schema = Schema::from_serde_schema(serde_json::from_value(schema).unwrap()).unwrap();
+        let config = FuzzConfig::new().with_number_strategy(NumberStrategy::Edges);
+        let mut rng = rand_pcg::Pcg32::seed_from_u64(8927);
+
+        (0..1000)
+            .map(|_| {
+                let instance = crate::fuzz_with_config(&schema, &config, &mut rng);
+                let errors = jtd::validate(&schema, &instance, Default::default()).unwrap();
+                assert!(errors.is_empty(), "{}", instance);
+                instance
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,280 @@
+use crate::HintGenerator;
+use rand::{Rng, RngCore};
+use regex_syntax::hir::{Class, Hir, HirKind, Look, Repetition};
+
+// The most characters a pattern may generate, not counting the extra repeats
+// of unbounded repetitions. Bounded repetitions multiply when they're nested,
+// so even a short pattern could otherwise ask for gigabytes.
+const MAX_LEN: usize = 10_000;
+
+// Generates strings matching a fuzzPattern.
+//
+// Generated strings are whole matches of the pattern, so a pattern matches
+// them whether or not it's anchored. That makes ^ and $ easy to honor at the
+// start and end of a pattern, but not elsewhere, such as in "a^b", and so
+// patterns with anchors that could be preceded or followed by text are
+// rejected, as are patterns with word boundaries.
+pub(crate) struct Pattern {
+    hir: Hir,
+
+    // How many more times than their minimum unbounded repetitions, like "*"
+    // and "+", may repeat.
+    max_extra_repeats: u32,
+}
+
+impl Pattern {
+    // Parses a pattern, or returns a description of why it can't be used.
+    pub(crate) fn parse(pattern: &str, max_extra_repeats: u32) -> Result<Self, String> {
+        let hir = regex_syntax::parse(pattern).map_err(|err| match err {
+            regex_syntax::Error::Parse(err) => err.kind().to_string(),
+            regex_syntax::Error::Translate(err) => err.kind().to_string(),
+            err => err.to_string(),
+        })?;
+
+        check(&hir, true, true)?;
+
+        if max_len(&hir) > MAX_LEN {
+            return Err(format!(
+                "pattern generates strings of more than {} characters",
+                MAX_LEN
+            ));
+        }
+
+        Ok(Self {
+            hir,
+            max_extra_repeats,
+        })
+    }
+}
+
+impl HintGenerator for Pattern {
+    fn generate(&self, rng: &mut dyn RngCore) -> String {
+        let mut out = String::new();
+        generate(&self.hir, self.max_extra_repeats, rng, &mut out);
+        out
+    }
+}
+
+// Checks that strings can be generated for hir. at_start and at_end say
+// whether hir is always at the start or end of generated strings.
+fn check(hir: &Hir, at_start: bool, at_end: bool) -> Result<(), String> {
+    match hir.kind() {
+        HirKind::Empty | HirKind::Literal(_) => Ok(()),
+
+        HirKind::Class(Class::Unicode(class)) if class.ranges().is_empty() => {
+            Err("character class matches nothing".to_owned())
+        }
+        HirKind::Class(Class::Bytes(class)) if class.ranges().is_empty() => {
+            Err("character class matches nothing".to_owned())
+        }
+        HirKind::Class(_) => Ok(()),
+
+        HirKind::Look(Look::Start | Look::StartLF | Look::StartCRLF) if !at_start => {
+            Err("start anchor that is not at the start of the pattern".to_owned())
+        }
+        HirKind::Look(Look::End | Look::EndLF | Look::EndCRLF) if !at_end => {
+            Err("end anchor that is not at the end of the pattern".to_owned())
+        }
+        HirKind::Look(
+            Look::Start | Look::StartLF | Look::StartCRLF | Look::End | Look::EndLF | Look::EndCRLF,
+        ) => Ok(()),
+        HirKind::Look(_) => Err("word boundaries are not supported".to_owned()),
+
+        // Every repetition after the first comes after another, unless the
+        // repeated expression only ever matches the empty string.
+        HirKind::Repetition(repetition) => {
+            let repeats = repetition.max != Some(1) && !always_empty(&repetition.sub);
+            check(&repetition.sub, at_start && !repeats, at_end && !repeats)
+        }
+
+        HirKind::Capture(capture) => check(&capture.sub, at_start, at_end),
+
+        HirKind::Concat(hirs) => {
+            for (i, hir) in hirs.iter().enumerate() {
+                check(
+                    hir,
+                    at_start && hirs[..i].iter().all(always_empty),
+                    at_end && hirs[i + 1..].iter().all(always_empty),
+                )?;
+            }
+
+            Ok(())
+        }
+
+        HirKind::Alternation(hirs) => {
+            for hir in hirs {
+                check(hir, at_start, at_end)?;
+            }
+
+            Ok(())
+        }
+    }
+}
+
+fn always_empty(hir: &Hir) -> bool {
+    hir.properties().maximum_len() == Some(0)
+}
+
+// The most characters generate writes for hir, if unbounded repetitions repeat
+// no more than their minimum. Saturates rather than overflowing.
+fn max_len(hir: &Hir) -> usize {
+    match hir.kind() {
+        HirKind::Empty | HirKind::Look(_) => 0,
+        HirKind::Literal(literal) => String::from_utf8_lossy(&literal.0).chars().count(),
+        HirKind::Class(_) => 1,
+        HirKind::Repetition(repetition) => {
+            max_len(&repetition.sub).saturating_mul(max_repeats(repetition, 0) as usize)
+        }
+        HirKind::Capture(capture) => max_len(&capture.sub),
+        HirKind::Concat(hirs) => hirs.iter().map(max_len).fold(0, usize::saturating_add),
+        HirKind::Alternation(hirs) => hirs.iter().map(max_len).max().unwrap_or(0),
+    }
+}
+
+// The most times a repetition repeats.
+fn max_repeats(repetition: &Repetition, max_extra_repeats: u32) -> u32 {
+    repetition
+        .max
+        .unwrap_or_else(|| repetition.min.saturating_add(max_extra_repeats))
+}
+
+fn generate(hir: &Hir, max_extra_repeats: u32, rng: &mut dyn RngCore, out: &mut String) {
+    match hir.kind() {
+        // Anchors hold wherever they're allowed, and generate nothing.
+        HirKind::Empty | HirKind::Look(_) => {}
+
+        // Parsing ensures that literals are valid UTF-8.
+        HirKind::Literal(literal) => out.push_str(&String::from_utf8_lossy(&literal.0)),
+
+        // Characters are chosen uniformly from the whole class, rather than
+        // from each range in turn.
+        HirKind::Class(Class::Unicode(class)) => {
+            let len = |start: char, end: char| end as u32 - start as u32 + 1;
+            let total: u32 = class.ranges().iter().map(|r| len(r.start(), r.end())).sum();
+
+            // Classes may span the surrogate code points, which aren't
+            // characters, so try again if one of them comes up.
+            loop {
+                let mut n = rng.gen_range(0..total);
+                for range in class.ranges() {
+                    if n < len(range.start(), range.end()) {
+                        if let Some(c) = char::from_u32(range.start() as u32 + n) {
+                            out.push(c);
+                            return;
+                        }
+
+                        break;
+                    }
+
+                    n -= len(range.start(), range.end());
+                }
+            }
+        }
+
+        // Parsing ensures that byte classes only have ASCII in them.
+        HirKind::Class(Class::Bytes(class)) => {
+            let len = |start: u8, end: u8| end as u32 - start as u32 + 1;
+            let total: u32 = class.ranges().iter().map(|r| len(r.start(), r.end())).sum();
+
+            let mut n = rng.gen_range(0..total);
+            for range in class.ranges() {
+                if n < len(range.start(), range.end()) {
+                    out.push((range.start() + n as u8) as char);
+                    return;
+                }
+
+                n -= len(range.start(), range.end());
+            }
+        }
+
+        HirKind::Repetition(repetition) => {
+            let max = max_repeats(repetition, max_extra_repeats);
+            for _ in 0..rng.gen_range(repetition.min..=max) {
+                generate(&repetition.sub, max_extra_repeats, rng, out);
+            }
+        }
+
+        HirKind::Capture(capture) => generate(&capture.sub, max_extra_repeats, rng, out),
+
+        HirKind::Concat(hirs) => {
+            for hir in hirs {
+                generate(hir, max_extra_repeats, rng, out);
+            }
+        }
+
+        HirKind::Alternation(hirs) => {
+            let hir = &hirs[rng.gen_range(0..hirs.len())];
+            generate(hir, max_extra_repeats, rng, out);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::SeedableRng;
+    use regex::Regex;
+
+    #[test]
+    fn test_pattern() {
+        let patterns = [
+            "^ORD-[0-9]{8}$",
+            "[A-Z]{2,3}-\\d+",
+            "^(foo|bar|baz)?$",
+            "(?i)^hello, (world|there)!$",
+            "^[^a-z]*$",
+            "^\\w+@\\w+\\.(com|org)$",
+            "^(\\p{Greek}|[\u{1f600}-\u{1f64f}])+$",
+            "^.{0,10}$",
+            "(?-u)^[\\x00-\\x7f]\\w$",
+            "^(a|^b)c$",
+            "((^))*x",
+            "a?b*c+",
+            "",
+        ];
+
+        let mut rng = rand_pcg::Pcg32::seed_from_u64(8927);
+        for pattern in &patterns {
+            let regex = Regex::new(pattern).unwrap();
+            let generator = Pattern::parse(pattern, 7).unwrap();
+
+            for _ in 0..1000 {
+                let s = generator.generate(&mut rng);
+                assert!(regex.is_match(&s), "{:?} does not match {:?}", s, pattern);
+            }
+        }
+    }
+
+    #[test]
+    fn test_pattern_errors() {
+        let errors = [
+            ("[a-", "unclosed character class"),
+            ("[^\\s\\S]", "character class matches nothing"),
+            (
+                "a^b",
+                "start anchor that is not at the start of the pattern",
+            ),
+            ("(a$)+", "end anchor that is not at the end of the pattern"),
+            (
+                "(^a)*",
+                "start anchor that is not at the start of the pattern",
+            ),
+            ("\\bword\\b", "word boundaries are not supported"),
+            (
+                "^(a{1000}){1000}{1000}$",
+                "pattern generates strings of more than 10000 characters",
+            ),
+            (
+                "[0-9]{10001}",
+                "pattern generates strings of more than 10000 characters",
+            ),
+        ];
+
+        for (pattern, expected) in &errors {
+            assert_eq!(
+                Err(expected.to_string()),
+                Pattern::parse(pattern, 7).map(|_| ())
+            );
+        }
+    }
+}
@@ -0,0 +1,394 @@
+use crate::words::{fill_bytes_via_u32, next_u64_via_u32, Replay};
+use crate::{fuzz_with_config, FuzzConfig};
+use jtd::Schema;
+use proptest::num;
+use proptest::strategy::{NewTree, Strategy, ValueTree};
+use proptest::test_runner::TestRunner;
+use rand::{RngCore, SeedableRng};
+use rand_pcg::Pcg32;
+use serde_json::Value;
+use std::sync::Arc;
+
+/// Returns a [`proptest`] strategy that generates instances of a schema.
+///
+/// This is only available with the `proptest` feature enabled. Instances are
+/// generated exactly as [`fuzz`](crate::fuzz) would, so they are always valid
+/// against `schema`.
+///
+/// When a test fails, proptest will shrink the failing instance towards
+/// smaller instances, which are also always valid against `schema`. Smaller
+/// instances have shorter arrays and strings, fewer optional properties,
+/// `null` wherever the schema is nullable, earlier (in sorted order) members of
+/// enums, and numbers closer to zero.
+///
+/// ```
+/// use proptest::prelude::*;
+/// use serde_json::json;
+///
+/// let schema = jtd::Schema::from_serde_schema(serde_json::from_value(json!({
+///     "elements": { "type": "uint8" }
+/// })).unwrap()).unwrap();
+///
+/// proptest!(|(instance in jtd_fuzz::strategy(&schema))| {
+///     prop_assert!(instance.as_array().unwrap().len() <= 7);
+/// });
+/// ```
+///
+/// # Shrinking
+///
+/// Strategies from this function work by recording the random numbers used to
+/// generate an instance, and then shrinking those numbers towards zero or
+/// removing them. Since an instance is regenerated from scratch each time
+/// those numbers change, shrinking can never produce an invalid instance.
+pub fn strategy(schema: &Schema) -> impl Strategy<Value = Value> {
+    strategy_with_config(schema, &FuzzConfig::default())
+}
+
+/// Returns a [`proptest`] strategy that generates instances of a schema, with
+/// a custom configuration.
+///
+/// See [`strategy`] for details. Shrunk instances are also generated using
+/// `config`, so they respect its limits.
+pub fn strategy_with_config(schema: &Schema, config: &FuzzConfig) -> impl Strategy<Value = Value> {
+    SchemaStrategy {
+        schema: Arc::new(schema.clone()),
+        config: Arc::new(config.clone().with_zero_words_smallest()),
+    }
+}
+
+#[derive(Debug)]
+struct SchemaStrategy {
+    schema: Arc<Schema>,
+    config: Arc<FuzzConfig>,
+}
+
+impl Strategy for SchemaStrategy {
+    type Tree = SchemaValueTree;
+    type Value = Value;
+
+    fn new_tree(&self, runner: &mut TestRunner) -> NewTree<Self> {
+        // proptest may use a different version of rand than this crate does,
+        // so rather than use its RNG directly, seed one of our own from it.
+        let seed = num::u64::ANY.new_tree(runner)?.current();
+        let mut rng = Recorder {
+            rng: Pcg32::seed_from_u64(seed),
+            words: vec![],
+        };
+
+        let value = fuzz_with_config(&self.schema, &self.config, &mut rng);
+
+        Ok(SchemaValueTree {
+            schema: self.schema.clone(),
+            config: self.config.clone(),
+            best: (rng.words, value),
+            trial: None,
+            pass: Pass::Delete(2),
+            index: 0,
+            lo: 0,
+            hi: 0,
+            tried: 0,
+            progress: false,
+        })
+    }
+}
+
+// Shrinks an instance by shrinking the random words used to generate it, in
+// passes over those words:
+//
+// * Deleting pairs of words, and then single words. This throws away values
+//   that were generated and then rejected, such as by rand's rejection
+//   sampling, and entire elements of arrays.
+//
+// * Binary searching each word for the smallest value that still fails the
+//   test, much like proptest does for integers.
+//
+// Both passes repeat until neither makes any progress.
+struct SchemaValueTree {
+    schema: Arc<Schema>,
+    config: Arc<FuzzConfig>,
+
+    // The words and value of the simplest instance known to fail the test.
+    best: (Vec<u32>, Value),
+
+    // The words and value being tested, if any.
+    trial: Option<(Vec<u32>, Value)>,
+
+    // The pass being made, and the index of the word it's at.
+    pass: Pass,
+    index: usize,
+
+    // When minimizing, the range the word at index is being searched in.
+    // Values below lo are known to pass the test, and hi is known to fail.
+    lo: u32,
+    hi: u32,
+    tried: u32,
+
+    // Whether any trial has failed the test since the deletion pass started.
+    progress: bool,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Pass {
+    Delete(usize),
+    Minimize,
+}
+
+impl SchemaValueTree {
+    // Moves on to the next simplification to try, if there are any left.
+    fn next_trial(&mut self) -> bool {
+        loop {
+            let len = self.best.0.len();
+            let mut words = self.best.0.clone();
+
+            match self.pass {
+                Pass::Delete(n) if self.index + n > len => {
+                    self.pass = if n > 1 {
+                        Pass::Delete(n - 1)
+                    } else {
+                        Pass::Minimize
+                    };
+
+                    self.seek(0);
+                    continue;
+                }
+
+                Pass::Delete(n) => {
+                    words.drain(self.index..self.index + n);
+                }
+
+                Pass::Minimize if self.index >= len => {
+                    if !self.progress {
+                        return false;
+                    }
+
+                    self.progress = false;
+                    self.pass = Pass::Delete(2);
+                    self.seek(0);
+                    continue;
+                }
+
+                Pass::Minimize if self.lo >= self.hi => {
+                    self.seek(self.index + 1);
+                    continue;
+                }
+
+                Pass::Minimize => {
+                    // Trying zero first makes it cheap to throw away entire
+                    // arrays and objects in one go.
+                    self.tried = if self.lo == 0 {
+                        0
+                    } else {
+                        self.lo + (self.hi - self.lo) / 2
+                    };
+
+                    words[self.index] = self.tried;
+                }
+            }
+
+            let mut rng = Replay { words, position: 0 };
+            let value = fuzz_with_config(&self.schema, &self.config, &mut rng);
+
+            // Drop the words the new instance didn't use, and keep any (zero)
+            // words it used beyond the end, so that the words always line up
+            // with the instance they generate.
+            let mut words = rng.words;
+            words.resize(rng.position, 0);
+
+            // If nothing changed, such as when deleting zeroes from the end,
+            // there's no need to run the test.
+            if words == self.best.0 {
+                self.advance();
+                continue;
+            }
+
+            self.trial = Some((words, value));
+            return true;
+        }
+    }
+
+    // Moves past a trial that passed the test.
+    fn advance(&mut self) {
+        match self.pass {
+            Pass::Delete(_) => self.index += 1,
+            Pass::Minimize => self.lo = self.tried + 1,
+        }
+    }
+
+    fn seek(&mut self, index: usize) {
+        self.index = index;
+        self.lo = 0;
+        self.hi = self.best.0.get(index).copied().unwrap_or(0);
+    }
+}
+
+impl ValueTree for SchemaValueTree {
+    type Value = Value;
+
+    fn current(&self) -> Value {
+        match self.trial {
+            Some((_, ref value)) => value.clone(),
+            None => self.best.1.clone(),
+        }
+    }
+
+    fn simplify(&mut self) -> bool {
+        // The current instance failed the test. If it was a trial, it's now the
+        // simplest known failing instance. Stay at the same index, because it
+        // may be possible to simplify it further.
+        if let Some(trial) = self.trial.take() {
+            self.best = trial;
+            self.progress = true;
+            self.hi = self.best.0.get(self.index).copied().unwrap_or(0);
+        }
+
+        self.next_trial()
+    }
+
+    fn complicate(&mut self) -> bool {
+        // The current instance passed the test, so the trial went too far.
+        match self.trial.take() {
+            Some(_) => {
+                self.advance();
+                self.next_trial()
+            }
+            None => false,
+        }
+    }
+}
+
+// An RNG that records the words it generates.
+struct Recorder<R> {
+    rng: R,
+    words: Vec<u32>,
+}
+
+impl<R: RngCore> RngCore for Recorder<R> {
+    fn next_u32(&mut self) -> u32 {
+        let word = self.rng.next_u32();
+        self.words.push(word);
+        word
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        next_u64_via_u32(self)
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        fill_bytes_via_u32(self, dest)
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), rand::Error> {
+        self.fill_bytes(dest);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{parse_schema, recursive_schema};
+    use proptest::test_runner::{Config, TestError};
+    use serde_json::json;
+
+    #[test]
+    fn test_strategy_valid() {
+        let schema = recursive_schema();
+
+        let mut runner = TestRunner::default();
+        for _ in 0..1000 {
+            let mut tree = strategy(&schema).new_tree(&mut runner).unwrap();
+
+            // Shrinking, whether or not tests pass, must never leave the
+            // schema.
+            for i in 0..100 {
+                let instance = tree.current();
+                let errors = jtd::validate(&schema, &instance, Default::default()).unwrap();
+                assert!(errors.is_empty(), "{}", instance);
+
+                let changed = if i % 3 == 0 {
+                    tree.complicate()
+                } else {
+                    tree.simplify()
+                };
+
+                if !changed {
+                    break;
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_strategy_shrink() {
+        let schema = parse_schema(json!({
+            "properties": {
+                "xs": { "elements": { "type": "uint8" }},
+            },
+            "optionalProperties": {
+                "y": { "type": "string" },
+            },
+        }));
+
+        assert_eq!(
+            json!({ "xs": [0, 0, 0] }),
+            shrink(&schema, |instance| instance["xs"].as_array().unwrap().len()
+                < 3),
+        );
+
+        let schema = parse_schema(json!({
+            "elements": { "enum": ["a", "b", "c"], "nullable": true },
+        }));
+
+        assert_eq!(
+            json!(["b"]),
+            shrink(&schema, |instance| !instance
+                .as_array()
+                .unwrap()
+                .contains(&json!("b"))),
+        );
+    }
+
+    #[test]
+    fn test_strategy_shrink_recursive() {
+        // Shrinking goes through refs, discriminators, and additional
+        // properties alike, down to the smallest instance that still fails.
+        assert_eq!(
+            json!({
+                "node": {
+                    "value": "1969-12-31T10:00:00-14:00",
+                    "children": [{ "value": "1969-12-31T10:00:00-14:00", "children": [] }],
+                },
+                "shape": null,
+                "any": null,
+                "count": 0,
+                "floats": [],
+            }),
+            shrink(&recursive_schema(), |instance| instance["node"]["children"]
+                .as_array()
+                .unwrap()
+                .is_empty()),
+        );
+    }
+
+    fn shrink(schema: &Schema, property: fn(&Value) -> bool) -> Value {
+        // Recursive instances can take many thousands of words to generate,
+        // and so more than proptest's default number of shrinking steps.
+        let mut runner = TestRunner::new(Config {
+            failure_persistence: None,
+            max_shrink_iters: 100_000,
+            ..Config::default()
+        });
+
+        match runner.run(&strategy(schema), |instance| {
+            assert!(jtd::validate(schema, &instance, Default::default())
+                .unwrap()
+                .is_empty());
+
+            proptest::prop_assert!(property(&instance));
+            Ok(())
+        }) {
+            Err(TestError::Fail(_, instance)) => instance,
+            result => panic!("property did not fail: {:?}", result),
+        }
+    }
+}
@@ -0,0 +1,655 @@
+#[cfg(feature = "cbor")]
+use crate::binary::{self, CborTimestamps};
+use crate::config::{gen_length, gen_probability};
+use crate::{
+    empty_cases, empty_elements_schema, empty_schema, empty_values_schema, fuzz_empty_primitive,
+    fuzz_type, strings, Context, FuzzConfig,
+};
+use jtd::Schema;
+#[cfg(feature = "cbor")]
+use jtd::Type;
+use rand::seq::{IteratorRandom, SliceRandom};
+use serde::ser::{Serialize, SerializeMap, SerializeSeq, Serializer};
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::convert::Infallible;
+use std::io;
+
+/// Generates a single random JSON value satisfying a given schema, and writes
+/// it to `writer`.
+///
+/// This writes exactly what serializing the output of [`fuzz`](crate::fuzz)
+/// would, given an RNG in the same state, and leaves the RNG in the same state
+/// afterwards. The difference is that the value is written out as it's
+/// generated, rather than being built up in memory first, so very large values
+/// can be generated without holding them in memory. Only the keys of the
+/// objects currently being written are kept around.
+///
+/// ```
+/// use serde_json::json;
+/// use rand::SeedableRng;
+///
+/// let schema = jtd::Schema::from_serde_schema(serde_json::from_value(json!({
+///     "properties": {
+///         "name": { "type": "string" },
+///         "favoriteNumbers": {
+///             "elements": { "type": "uint8" }
+///         }
+///     }
+/// })).unwrap()).unwrap();
+///
+/// let mut rng = rand_pcg::Pcg32::seed_from_u64(8927);
+/// let mut out = Vec::new();
+/// jtd_fuzz::fuzz_to_writer(&schema, &mut rng, &mut out).unwrap();
+///
+/// let mut rng = rand_pcg::Pcg32::seed_from_u64(8927);
+/// assert_eq!(
+///     String::from_utf8(out).unwrap(),
+///     jtd_fuzz::fuzz(&schema, &mut rng).to_string(),
+/// );
+/// ```
+///
+/// Objects are written with their keys in sorted order, just like
+/// [`serde_json::Value`] does. Since properties aren't generated in that
+/// order, generating an object means generating each of its members twice:
+/// once to find out what the keys are, and once more to write them out in
+/// order. That makes this function slower than [`fuzz`](crate::fuzz) for
+/// deeply nested objects, which is why the RNG must implement [`Clone`].
+pub fn fuzz_to_writer<R: rand::Rng + Clone, W: io::Write>(
+    schema: &Schema,
+    rng: &mut R,
+    writer: W,
+) -> serde_json::Result<()> {
+    fuzz_to_writer_with_config(schema, &FuzzConfig::default(), rng, writer)
+}
+
+/// Generates a single random JSON value satisfying a given schema, using the
+/// given configuration, and writes it to `writer`.
+///
+/// See [`fuzz_to_writer`] for details.
+pub fn fuzz_to_writer_with_config<R: rand::Rng + Clone, W: io::Write>(
+    schema: &Schema,
+    config: &FuzzConfig,
+    rng: &mut R,
+    writer: W,
+) -> serde_json::Result<()> {
+    fuzz_to_serializer_with_config(
+        schema,
+        config,
+        rng,
+        &mut serde_json::Serializer::new(writer),
+    )
+}
+
+/// Generates a single random JSON value satisfying a given schema, and passes
+/// it to a [`serde::Serializer`] as it's generated.
+///
+/// This is like [`fuzz_to_writer`], except that it works with any data format
+/// that has a serde `Serializer`, or with a `serde_json::Serializer` with a
+/// different formatter. For instance, to write out pretty-printed JSON:
+///
+/// ```
+/// use serde_json::json;
+/// use rand::SeedableRng;
+///
+/// let schema = jtd::Schema::from_serde_schema(serde_json::from_value(json!({
+///     "elements": { "type": "uint8" }
+/// })).unwrap()).unwrap();
+///
+/// let mut rng = rand_pcg::Pcg32::seed_from_u64(8927);
+/// let mut out = Vec::new();
+/// let mut serializer = serde_json::Serializer::pretty(&mut out);
+/// jtd_fuzz::fuzz_to_serializer(&schema, &mut rng, &mut serializer).unwrap();
+///
+/// let mut rng = rand_pcg::Pcg32::seed_from_u64(8927);
+/// assert_eq!(
+///     String::from_utf8(out).unwrap(),
+///     serde_json::to_string_pretty(&jtd_fuzz::fuzz(&schema, &mut rng)).unwrap(),
+/// );
+/// ```
+pub fn fuzz_to_serializer<R: rand::Rng + Clone, S: Serializer>(
+    schema: &Schema,
+    rng: &mut R,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    fuzz_to_serializer_with_config(schema, &FuzzConfig::default(), rng, serializer)
+}
+
+/// Generates a single random JSON value satisfying a given schema, using the
+/// given configuration, and passes it to a [`serde::Serializer`] as it's
+/// generated.
+///
+/// See [`fuzz_to_serializer`] and [`fuzz_to_writer`] for details.
+pub fn fuzz_to_serializer_with_config<R: rand::Rng + Clone, S: Serializer>(
+    schema: &Schema,
+    config: &FuzzConfig,
+    rng: &mut R,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    serialize_with_style(schema, config, Style::JSON, rng, serializer)
+}
+
+// How a value is written out.
+#[derive(Clone, Copy)]
+pub(crate) struct Style {
+    // If this is false, object members are written in the order they're
+    // generated, rather than sorted by key, and may repeat keys. That's only
+    // useful for skipping over values; see Sorted.
+    pub(crate) sorted: bool,
+
+    // How timestamps are written. Anything other than text only makes sense
+    // for CBOR.
+    #[cfg(feature = "cbor")]
+    pub(crate) timestamps: CborTimestamps,
+}
+
+impl Style {
+    pub(crate) const JSON: Style = Style {
+        sorted: true,
+        #[cfg(feature = "cbor")]
+        timestamps: CborTimestamps::Text,
+    };
+
+    const SKIP: Style = Style {
+        sorted: false,
+        #[cfg(feature = "cbor")]
+        timestamps: CborTimestamps::Text,
+    };
+}
+
+// Generates a value, passing it to serializer in the given style as it goes.
+pub(crate) fn serialize_with_style<R: rand::Rng + Clone, S: Serializer>(
+    schema: &Schema,
+    config: &FuzzConfig,
+    style: Style,
+    rng: &mut R,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    serialize_with_root(
+        &mut Context::new(config, schema),
+        rng,
+        schema,
+        style,
+        serializer,
+    )
+}
+
+// The streaming counterpart of fuzz_with_root. Each case makes the same random
+// choices, in the same order, as the same case in fuzz_with_root.
+fn serialize_with_root<'a, R: rand::Rng + Clone, S: Serializer>(
+    cx: &mut Context<'a>,
+    rng: &mut R,
+    schema: &'a Schema,
+    style: Style,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    let config = cx.config;
+
+    match schema {
+        Schema::Empty { .. } => match rng.gen_range(0..empty_cases(cx, schema)) {
+            5 => {
+                let schema = empty_elements_schema();
+                let cx = &mut Context::new(config, &schema);
+                serialize_with_root(cx, rng, &schema, style, serializer)
+            }
+
+            6 => {
+                let schema = empty_values_schema();
+                let cx = &mut Context::new(config, &schema);
+                serialize_with_root(cx, rng, &schema, style, serializer)
+            }
+
+            case => fuzz_empty_primitive(config, rng, case).serialize(serializer),
+        },
+
+        Schema::Ref {
+            ref ref_, nullable, ..
+        } => {
+            if cx.gen_null(rng, *nullable) {
+                return serializer.serialize_unit();
+            }
+
+            let recursive = cx.refs.contains(&ref_.as_str());
+            cx.refs.push(ref_);
+            if recursive {
+                cx.recursion_depth += 1;
+            }
+
+            let root = cx.root;
+            let result = serialize_with_root(cx, rng, &root.definitions()[ref_], style, serializer);
+
+            cx.refs.pop();
+            if recursive {
+                cx.recursion_depth -= 1;
+            }
+
+            result
+        }
+
+        Schema::Type {
+            ref metadata,
+            ref type_,
+            nullable,
+            ..
+        } => {
+            if cx.gen_null(rng, *nullable) {
+                return serializer.serialize_unit();
+            }
+
+            let value = fuzz_type(cx, rng, metadata, type_);
+            match type_ {
+                #[cfg(feature = "cbor")]
+                Type::Timestamp => binary::serialize_timestamp(
+                    value.as_str().unwrap(),
+                    style.timestamps,
+                    serializer,
+                ),
+                _ => value.serialize(serializer),
+            }
+        }
+
+        Schema::Enum {
+            ref enum_,
+            nullable,
+            ..
+        } => {
+            if cx.gen_null(rng, *nullable) {
+                return serializer.serialize_unit();
+            }
+
+            serializer.serialize_str(enum_.iter().choose(rng).unwrap())
+        }
+
+        Schema::Elements {
+            ref elements,
+            nullable,
+            ..
+        } => {
+            if cx.gen_null(rng, *nullable) {
+                return serializer.serialize_unit();
+            }
+
+            let len = if cx.exhausted() {
+                0
+            } else {
+                gen_length(rng, &config.elements_length)
+            };
+
+            let mut seq = serializer.serialize_seq(Some(len))?;
+            for _ in 0..len {
+                seq.serialize_element(&Fuzzed::new(cx, rng, Member::Fuzzed(elements), style))?;
+            }
+
+            seq.end()
+        }
+
+        Schema::Properties { nullable, .. } => {
+            if cx.gen_null(rng, *nullable) {
+                return serializer.serialize_unit();
+            }
+
+            serialize_object(cx, rng, Object::Properties(schema), style, serializer)
+        }
+
+        Schema::Values {
+            ref values,
+            nullable,
+            ..
+        } => {
+            if cx.gen_null(rng, *nullable) {
+                return serializer.serialize_unit();
+            }
+
+            serialize_object(cx, rng, Object::Values(values), style, serializer)
+        }
+
+        Schema::Discriminator {
+            ref mapping,
+            ref discriminator,
+            nullable,
+            ..
+        } => {
+            if cx.gen_null(rng, *nullable) {
+                return serializer.serialize_unit();
+            }
+
+            let (discriminator_value, sub_schema) = *cx.variants(mapping).choose(rng).unwrap();
+            let object = Object::Discriminator(sub_schema, discriminator, discriminator_value);
+            serialize_object(cx, rng, object, style, serializer)
+        }
+    }
+}
+
+// A schema that generates an object, and isn't null.
+#[derive(Clone, Copy)]
+enum Object<'a> {
+    Properties(&'a Schema),
+
+    // The schema for each of the values.
+    Values(&'a Schema),
+
+    // The chosen variant, and the discriminator and its tag.
+    Discriminator(&'a Schema, &'a str, &'a str),
+}
+
+// A member of an object, and how to generate its value.
+#[derive(Clone, Copy)]
+enum Member<'a> {
+    Fuzzed(&'a Schema),
+
+    // An additional property. These are generated with a context of their own.
+    Additional,
+
+    // A discriminator's tag, which isn't random.
+    Tag(&'a str),
+}
+
+// Receives the members of an object as they're generated.
+trait MemberSink<'a, R> {
+    type Error;
+
+    // Called with rng just before the member's value is generated. This must
+    // advance rng past the value.
+    fn member(
+        &mut self,
+        cx: &mut Context<'a>,
+        rng: &mut R,
+        key: String,
+        member: Member<'a>,
+    ) -> Result<(), Self::Error>;
+}
+
+// Writes an object. When style.sorted is true, its members are generated once to find
+// out what the keys are, and then again, in key order, to write them out.
+fn serialize_object<'a, R: rand::Rng + Clone, S: Serializer>(
+    cx: &mut Context<'a>,
+    rng: &mut R,
+    object: Object<'a>,
+    style: Style,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    if style.sorted {
+        let mut sink = Sorted {
+            members: BTreeMap::new(),
+        };
+        object_members(cx, rng, object, &mut sink).unwrap_or_else(|never| match never {});
+
+        let mut map = serializer.serialize_map(Some(sink.members.len()))?;
+        for (key, (member, mut rng)) in sink.members {
+            map.serialize_entry(&key, &Fuzzed::new(cx, &mut rng, member, style))?;
+        }
+
+        map.end()
+    } else {
+        let mut sink = Unsorted(serializer.serialize_map(None)?);
+        object_members(cx, rng, object, &mut sink)?;
+        sink.0.end()
+    }
+}
+
+// Generates the members of an object, in the same way as fuzz_with_root.
+fn object_members<'a, R: rand::Rng + Clone, K: MemberSink<'a, R>>(
+    cx: &mut Context<'a>,
+    rng: &mut R,
+    object: Object<'a>,
+    sink: &mut K,
+) -> Result<(), K::Error> {
+    let config = cx.config;
+
+    let schema = match object {
+        Object::Properties(schema) => schema,
+
+        Object::Values(values) => {
+            let len = if cx.exhausted() {
+                0
+            } else {
+                gen_length(rng, &config.values_length)
+            };
+
+            for _ in 0..len {
+                let key = strings::fuzz_string(config, rng);
+                sink.member(cx, rng, key, Member::Fuzzed(values))?;
+            }
+
+            return Ok(());
+        }
+
+        Object::Discriminator(sub_schema, discriminator, discriminator_value) => {
+            // Mapping values are always non-nullable properties schemas, so
+            // there's no null to generate here. The tag comes last, so that it
+            // replaces any additional property with the same name.
+            object_members(cx, rng, Object::Properties(sub_schema), sink)?;
+            return sink.member(
+                cx,
+                rng,
+                discriminator.to_owned(),
+                Member::Tag(discriminator_value),
+            );
+        }
+    };
+
+    let (properties, optional_properties, additional_properties) = match schema {
+        Schema::Properties {
+            ref properties,
+            ref optional_properties,
+            additional_properties,
+            ..
+        } => (properties, optional_properties, *additional_properties),
+        _ => panic!("discriminator mapping values must be properties schemas"),
+    };
+
+    let mut required_keys: Vec<_> = properties.keys().collect();
+    required_keys.sort();
+
+    for k in required_keys {
+        sink.member(cx, rng, k.clone(), Member::Fuzzed(&properties[k]))?;
+    }
+
+    let mut optional_keys: Vec<_> = optional_properties.keys().collect();
+    optional_keys.sort();
+
+    for k in optional_keys {
+        if cx.exhausted()
+            || gen_probability(config, rng, 1.0 - config.optional_property_probability)
+        {
+            continue;
+        }
+
+        sink.member(cx, rng, k.clone(), Member::Fuzzed(&optional_properties[k]))?;
+    }
+
+    if additional_properties && !cx.exhausted() {
+        let defined =
+            strings::DefinedProperties::new(properties.keys().chain(optional_properties.keys()));
+
+        for _ in 0..gen_length(rng, &config.additional_properties_length) {
+            if let Some(key) = strings::fuzz_additional_key(config, rng, &defined) {
+                sink.member(cx, rng, key, Member::Additional)?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+// Collects members, along with the RNG needed to generate them again, sorted by
+// key. Just like a serde_json::Map, later members replace earlier ones with the
+// same key.
+struct Sorted<'a, R> {
+    members: BTreeMap<String, (Member<'a>, R)>,
+}
+
+impl<'a, R: rand::Rng + Clone> MemberSink<'a, R> for Sorted<'a, R> {
+    type Error = Infallible;
+
+    fn member(
+        &mut self,
+        cx: &mut Context<'a>,
+        rng: &mut R,
+        key: String,
+        member: Member<'a>,
+    ) -> Result<(), Infallible> {
+        self.members.insert(key, (member, rng.clone()));
+
+        // Skip over the value, without keeping it around.
+        serialize_member(
+            cx,
+            rng,
+            member,
+            Style::SKIP,
+            &mut serde_json::Serializer::new(io::sink()),
+        )
+        .expect("writing to a sink never fails");
+
+        Ok(())
+    }
+}
+
+// Writes members out as soon as they're generated.
+struct Unsorted<M>(M);
+
+impl<'a, R: rand::Rng + Clone, M: SerializeMap> MemberSink<'a, R> for Unsorted<M> {
+    type Error = M::Error;
+
+    fn member(
+        &mut self,
+        cx: &mut Context<'a>,
+        rng: &mut R,
+        key: String,
+        member: Member<'a>,
+    ) -> Result<(), M::Error> {
+        self.0
+            .serialize_entry(&key, &Fuzzed::new(cx, rng, member, Style::SKIP))
+    }
+}
+
+fn serialize_member<'a, R: rand::Rng + Clone, S: Serializer>(
+    cx: &mut Context<'a>,
+    rng: &mut R,
+    member: Member<'a>,
+    style: Style,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    match member {
+        Member::Fuzzed(schema) => serialize_with_root(cx, rng, schema, style, serializer),
+        Member::Additional => {
+            let schema = empty_schema();
+            let cx = &mut Context::new(cx.config, &schema);
+            serialize_with_root(cx, rng, &schema, style, serializer)
+        }
+        Member::Tag(tag) => serializer.serialize_str(tag),
+    }
+}
+
+// A member that's generated as it's serialized.
+struct Fuzzed<'c, 'a, R> {
+    state: RefCell<(&'c mut Context<'a>, &'c mut R)>,
+    member: Member<'a>,
+    style: Style,
+}
+
+impl<'c, 'a, R> Fuzzed<'c, 'a, R> {
+    fn new(cx: &'c mut Context<'a>, rng: &'c mut R, member: Member<'a>, style: Style) -> Self {
+        Self {
+            state: RefCell::new((cx, rng)),
+            member,
+            style,
+        }
+    }
+}
+
+impl<'c, 'a, R: rand::Rng + Clone> Serialize for Fuzzed<'c, 'a, R> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let (cx, rng) = &mut *self.state.borrow_mut();
+        serialize_member(cx, *rng, self.member, self.style, serializer)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{assert_matches_fuzz, parse_schema, recursive_schema};
+    use rand::{Rng, SeedableRng};
+    use serde_json::{json, Value};
+
+    // Short keys make duplicate keys, and keys that clash with the
+    // discriminator, more likely.
+    fn short_keys() -> FuzzConfig {
+        FuzzConfig::new()
+            .with_string_length(0..=1)
+            .with_char_classes(vec![crate::CharClass::PrintableAscii])
+    }
+
+    #[test]
+    fn test_fuzz_to_writer() {
+        let to_string = |instance: Value| instance.to_string();
+
+        for config in &[FuzzConfig::new(), short_keys()] {
+            assert_matches_fuzz(&recursive_schema(), config, 200, to_string, |rng| {
+                let mut out = Vec::new();
+                fuzz_to_writer_with_config(&recursive_schema(), config, rng, &mut out).unwrap();
+                String::from_utf8(out).unwrap()
+            });
+
+            // Empty schemas at the root may generate arrays and objects too.
+            let schema = parse_schema(json!({}));
+            assert_matches_fuzz(&schema, config, 1000, to_string, |rng| {
+                let mut out = Vec::new();
+                fuzz_to_writer_with_config(&schema, config, rng, &mut out).unwrap();
+                String::from_utf8(out).unwrap()
+            });
+        }
+    }
+
+    #[test]
+    fn test_unsorted() {
+        // Without sorting, members come out in the order they're generated,
+        // so an additional property may repeat a key that came before it. The
+        // last of them is the one fuzz keeps.
+        let schema = parse_schema(json!({
+            "properties": {
+                "a": { "type": "uint8" },
+            },
+            "optionalProperties": {
+                "b": { "values": { "type": "uint8" }},
+            },
+            "additionalProperties": true,
+        }));
+        let config = short_keys()
+            .with_values_length(2..=7)
+            .with_additional_properties_length(2..=7);
+
+        let mut rng = rand_pcg::Pcg32::seed_from_u64(8927);
+        let mut stream_rng = rng.clone();
+        let mut repeats = 0;
+        for _ in 0..300 {
+            let instance = crate::fuzz_with_config(&schema, &config, &mut rng);
+
+            let mut out = Vec::new();
+            let serializer = &mut serde_json::Serializer::new(&mut out);
+            serialize_with_style(
+                &schema,
+                &config,
+                Style::SKIP,
+                &mut stream_rng.clone(),
+                serializer,
+            )
+            .unwrap();
+            if out.len() > instance.to_string().len() {
+                repeats += 1;
+            }
+
+            let serializer = serde_json::value::Serializer;
+            let unsorted =
+                serialize_with_style(&schema, &config, Style::SKIP, &mut stream_rng, serializer)
+                    .unwrap();
+            assert_eq!(instance, unsorted);
+
+            // Values that are skipped must still use up the same random
+            // numbers.
+            assert_eq!(rng.gen::<u64>(), stream_rng.gen::<u64>());
+        }
+
+        assert!(repeats > 0);
+    }
+}
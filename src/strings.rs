@@ -0,0 +1,466 @@
+use crate::config::gen_length;
+use crate::encode::Encoder;
+use crate::FuzzConfig;
+use rand::seq::SliceRandom;
+use regex_syntax::hir::{ClassUnicode, ClassUnicodeRange};
+use std::collections::BTreeSet;
+use std::ops::RangeInclusive;
+
+/// A class of characters that generated strings may draw from.
+///
+/// See [`FuzzConfig::with_char_classes`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CharClass {
+    /// Printable ASCII characters, from space (`U+0020`) through tilde
+    /// (`U+007E`).
+    PrintableAscii,
+
+    /// Any character in the Basic Multilingual Plane (`U+0000` through
+    /// `U+FFFF`).
+    Bmp,
+
+    /// Characters outside of the Basic Multilingual Plane, such as emoji. In
+    /// UTF-16, these are written as a surrogate pair.
+    Astral,
+
+    /// Combining marks, such as `U+0301 COMBINING ACUTE ACCENT`. These combine
+    /// with whatever character precedes them, or with each other, and may
+    /// even begin a string.
+    Combining,
+
+    /// Right-to-left text, in Hebrew or Arabic, as well as the invisible
+    /// characters that override the direction of text.
+    Rtl,
+
+    /// Zero-width characters, such as `U+200B ZERO WIDTH SPACE` and the byte
+    /// order mark (`U+FEFF`).
+    ZeroWidth,
+
+    /// Control characters, such as `U+0000` and newline. The ones below
+    /// `U+0020` must be escaped in JSON.
+    Control,
+
+    /// Characters that can be written in more than one way in Unicode, written
+    /// in any of those ways. For instance, `é` may be generated either as
+    /// `U+00E9` (as in NFC) or as `e` followed by `U+0301` (as in NFD).
+    Normalization,
+}
+
+// Groups of canonically equivalent spellings of the same text.
+const EQUIVALENTS: &[&[&str]] = &[
+    &["\u{e9}", "e\u{301}"],
+    &["\u{f1}", "n\u{303}"],
+    &["\u{fc}", "u\u{308}"],
+    &["\u{e7}", "c\u{327}"],
+    &["\u{c5}", "\u{212b}", "A\u{30a}"],
+    &["\u{3a9}", "\u{2126}"],
+    &["\u{1e69}", "s\u{323}\u{307}", "s\u{307}\u{323}"],
+    &["\u{d55c}", "\u{1112}\u{1161}\u{11ab}"],
+];
+
+const ZERO_WIDTH: &[char] = &['\u{200b}', '\u{200c}', '\u{200d}', '\u{2060}', '\u{feff}'];
+
+// Characters that override the direction of the text around them.
+const BIDI_CONTROLS: &[char] = &[
+    '\u{200e}', '\u{200f}', '\u{202a}', '\u{202b}', '\u{202c}', '\u{202d}', '\u{202e}', '\u{2066}',
+    '\u{2067}', '\u{2068}', '\u{2069}',
+];
+
+// The ranges of characters that each class draws from.
+const PRINTABLE_ASCII: RangeInclusive<char> = ' '..='~';
+const ASCII_DELETE: u8 = 0x7f;
+const BMP: RangeInclusive<char> = '\u{0}'..='\u{ffff}';
+const EMOJI: RangeInclusive<char> = '\u{1f300}'..='\u{1faff}';
+const ASTRAL: RangeInclusive<char> = '\u{10000}'..='\u{10ffff}';
+const COMBINING: RangeInclusive<char> = '\u{300}'..='\u{36f}';
+const HEBREW: RangeInclusive<char> = '\u{5d0}'..='\u{5ea}';
+const ARABIC: RangeInclusive<char> = '\u{621}'..='\u{64a}';
+const C0_CONTROLS: RangeInclusive<char> = '\u{0}'..='\u{1f}';
+const C1_CONTROLS: RangeInclusive<char> = '\u{7f}'..='\u{9f}';
+
+pub(crate) fn fuzz_string<R: rand::Rng>(config: &FuzzConfig, rng: &mut R) -> String {
+    let len = gen_length(rng, &config.string_length);
+
+    // Some classes generate more than one character at a time, so the last of
+    // those may be cut short to keep the string at exactly len characters.
+    let mut chars = Vec::with_capacity(len);
+    while chars.len() < len {
+        fuzz_chars(choose_class(config, rng), rng, &mut chars);
+    }
+
+    chars.into_iter().take(len).collect()
+}
+
+// Chooses the class of the next characters in a string. With just one class,
+// as in the default configuration, no random numbers are used, so that strings
+// use the same random numbers as they did before there were classes.
+fn choose_class<R: rand::Rng>(config: &FuzzConfig, rng: &mut R) -> CharClass {
+    match config.char_classes[..] {
+        [class] => class,
+        _ => *config.char_classes.choose(rng).unwrap(),
+    }
+}
+
+fn fuzz_chars<R: rand::Rng>(class: CharClass, rng: &mut R, out: &mut Vec<char>) {
+    match class {
+        CharClass::PrintableAscii => out.push(loop {
+            // fuzz() has always chosen these as bytes from space through DEL,
+            // so that's still how they're chosen, for the same characters
+            // from the same seed. DEL isn't printable, though, so it's
+            // skipped.
+            let c = rng.gen_range(b' '..=ASCII_DELETE) as char;
+            if PRINTABLE_ASCII.contains(&c) {
+                break c;
+            }
+        }),
+        CharClass::Bmp => out.push(rng.gen_range(BMP)),
+        CharClass::Astral => {
+            // Most astral characters are unassigned, so make sure emoji come up
+            // often too.
+            out.push(if rng.gen() {
+                rng.gen_range(EMOJI)
+            } else {
+                rng.gen_range(ASTRAL)
+            })
+        }
+        CharClass::Combining => out.push(rng.gen_range(COMBINING)),
+        CharClass::Rtl => out.push(match rng.gen_range(0..3) {
+            0 => rng.gen_range(HEBREW),
+            1 => rng.gen_range(ARABIC),
+            _ => *BIDI_CONTROLS.choose(rng).unwrap(),
+        }),
+        CharClass::ZeroWidth => out.push(*ZERO_WIDTH.choose(rng).unwrap()),
+        CharClass::Control => out.push(match rng.gen_range(0..3) {
+            0 | 1 => rng.gen_range(C0_CONTROLS),
+            _ => rng.gen_range(C1_CONTROLS),
+        }),
+        CharClass::Normalization => {
+            let spellings = EQUIVALENTS.choose(rng).unwrap();
+            out.extend(spellings.choose(rng).unwrap().chars())
+        }
+    }
+}
+
+// The properties an object schema defines, for keeping additional properties
+// from colliding with them.
+//
+// Go's encoding/json package, which implements JSON serialization and
+// deserialization, matches object keys to struct fields case-insensitively,
+// using Unicode simple case folding. So that generated data is compatible with
+// Go, additional properties are never equal to a defined property under that
+// folding. Keys may draw from any of the configured character classes, so
+// folding ASCII alone isn't enough: "ſ" folds to "s", and the Kelvin sign to
+// "k", for instance.
+#[derive(Clone, Debug)]
+pub(crate) struct DefinedProperties(BTreeSet<String>);
+
+impl DefinedProperties {
+    pub(crate) fn new<'a, I: IntoIterator<Item = &'a String>>(properties: I) -> Self {
+        Self(properties.into_iter().map(|k| fold_case(k)).collect())
+    }
+
+    pub(crate) fn collides(&self, key: &str) -> bool {
+        self.0.contains(&fold_case(key))
+    }
+}
+
+// Generates the key of an additional property, or returns None if the key
+// that was generated collides with a defined property.
+pub(crate) fn fuzz_additional_key<R: rand::Rng>(
+    config: &FuzzConfig,
+    rng: &mut R,
+    defined: &DefinedProperties,
+) -> Option<String> {
+    let key = fuzz_string(config, rng);
+    if defined.collides(&key) {
+        None
+    } else {
+        Some(key)
+    }
+}
+
+// Replaces each character with the smallest character it's equivalent to under
+// simple case folding, so that two strings are equal after folding exactly when
+// Go's strings.EqualFold says they are.
+fn fold_case(s: &str) -> String {
+    s.chars()
+        .map(|c| {
+            // The only characters outside of ASCII that fold to ASCII letters
+            // are greater than them, so uppercase ASCII is always the
+            // smallest.
+            if c.is_ascii() {
+                return c.to_ascii_uppercase();
+            }
+
+            let mut class = ClassUnicode::new(vec![ClassUnicodeRange::new(c, c)]);
+            class.case_fold_simple();
+            class.ranges()[0].start()
+        })
+        .collect()
+}
+
+// The inverse of fuzz_string, for encode().
+pub(crate) fn encode_string(
+    config: &FuzzConfig,
+    enc: &mut Encoder,
+    s: &str,
+) -> Result<(), &'static str> {
+    let chars: Vec<char> = s.chars().collect();
+    if !config.string_length.contains(&chars.len()) {
+        return Err("string length is outside of the configured range");
+    }
+
+    enc.length(chars.len(), &config.string_length);
+
+    let mut rest = &chars[..];
+    while !rest.is_empty() {
+        // Of the classes that could have generated the next characters, use
+        // the one that generates the most of them at once.
+        let (len, index, class_enc) = config
+            .char_classes
+            .iter()
+            .enumerate()
+            .filter_map(|(index, class)| {
+                let mut class_enc = Encoder::new();
+                let len = encode_chars(*class, &mut class_enc, rest)?;
+                Some((len, index, class_enc))
+            })
+            .max_by_key(|(len, index, _)| (*len, std::cmp::Reverse(*index)))
+            .ok_or("string has characters outside of the configured classes")?;
+
+        if config.char_classes.len() > 1 {
+            enc.index(index, config.char_classes.len());
+        }
+        enc.append(class_enc);
+        rest = &rest[len..];
+    }
+
+    Ok(())
+}
+
+// The inverse of fuzz_chars. Returns how many of chars were encoded, if any.
+fn encode_chars(class: CharClass, enc: &mut Encoder, chars: &[char]) -> Option<usize> {
+    let c = chars[0];
+    match class {
+        CharClass::PrintableAscii => {
+            if !PRINTABLE_ASCII.contains(&c) {
+                return None;
+            }
+
+            // See fuzz_chars for why DEL is in the range.
+            enc.range_u32(c as u32 - ' ' as u32, (ASCII_DELETE - b' ') as u32);
+            Some(1)
+        }
+        CharClass::Bmp => encode_char(enc, c, BMP),
+        CharClass::Astral => {
+            enc.bool(EMOJI.contains(&c));
+            encode_char(enc, c, if EMOJI.contains(&c) { EMOJI } else { ASTRAL })
+        }
+        CharClass::Combining => encode_char(enc, c, COMBINING),
+        CharClass::Rtl => {
+            if let Some(index) = BIDI_CONTROLS.iter().position(|&b| b == c) {
+                enc.range_u32(2, 2);
+                enc.index(index, BIDI_CONTROLS.len());
+                Some(1)
+            } else if HEBREW.contains(&c) {
+                enc.range_u32(0, 2);
+                encode_char(enc, c, HEBREW)
+            } else {
+                enc.range_u32(1, 2);
+                encode_char(enc, c, ARABIC)
+            }
+        }
+        CharClass::ZeroWidth => {
+            let index = ZERO_WIDTH.iter().position(|&z| z == c)?;
+            enc.index(index, ZERO_WIDTH.len());
+            Some(1)
+        }
+        CharClass::Control => {
+            if C0_CONTROLS.contains(&c) {
+                enc.range_u32(0, 2);
+                encode_char(enc, c, C0_CONTROLS)
+            } else {
+                enc.range_u32(2, 2);
+                encode_char(enc, c, C1_CONTROLS)
+            }
+        }
+        CharClass::Normalization => {
+            // A spelling may have been cut short at the end of the string.
+            let (len, group, spelling) = EQUIVALENTS
+                .iter()
+                .enumerate()
+                .flat_map(|(group, spellings)| {
+                    spellings
+                        .iter()
+                        .enumerate()
+                        .filter_map(move |(spelling, s)| {
+                            let s: Vec<char> = s.chars().collect();
+                            if chars.starts_with(&s) {
+                                Some((s.len(), group, spelling))
+                            } else if s.starts_with(chars) {
+                                Some((chars.len(), group, spelling))
+                            } else {
+                                None
+                            }
+                        })
+                })
+                .max_by_key(|(len, _, _)| *len)?;
+
+            enc.index(group, EQUIVALENTS.len());
+            enc.index(spelling, EQUIVALENTS[group].len());
+            Some(len)
+        }
+    }
+}
+
+// The inverse of rng.gen_range(range) for a char.
+fn encode_char(enc: &mut Encoder, c: char, range: RangeInclusive<char>) -> Option<usize> {
+    if !range.contains(&c) {
+        return None;
+    }
+
+    // rand skips over the surrogate code points, which aren't valid chars.
+    let skip_surrogates = |c: char| match c as u32 {
+        n if n >= 0xd800 => n - 0x800,
+        n => n,
+    };
+
+    let low = skip_surrogates(*range.start());
+    enc.range_u32(
+        skip_surrogates(c) - low,
+        skip_surrogates(*range.end()) - low,
+    );
+
+    Some(1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use jtd::Schema;
+    use rand::SeedableRng;
+    use serde_json::json;
+
+    type IsInClass = fn(char) -> bool;
+
+    #[test]
+    fn test_printable_ascii() {
+        for s in fuzz_strings(&FuzzConfig::new()) {
+            assert!(s.chars().all(|c| (' '..='~').contains(&c)), "{:?}", s);
+        }
+    }
+
+    #[test]
+    fn test_char_classes() {
+        let classes: &[(CharClass, IsInClass)] = &[
+            (CharClass::PrintableAscii, |c| (' '..='~').contains(&c)),
+            (CharClass::Bmp, |c| c <= '\u{ffff}'),
+            (CharClass::Astral, |c| c > '\u{ffff}'),
+            (CharClass::Combining, |c| {
+                ('\u{300}'..='\u{36f}').contains(&c)
+            }),
+            (CharClass::Rtl, |c| {
+                ('\u{5d0}'..='\u{64a}').contains(&c) || BIDI_CONTROLS.contains(&c)
+            }),
+            (CharClass::ZeroWidth, |c| ZERO_WIDTH.contains(&c)),
+            (CharClass::Control, char::is_control),
+            (CharClass::Normalization, |c| {
+                EQUIVALENTS.iter().any(|e| e.iter().any(|s| s.contains(c)))
+            }),
+        ];
+
+        for (class, is_in_class) in classes {
+            let config = FuzzConfig::new().with_char_classes(vec![*class]);
+            let strings = fuzz_strings(&config);
+
+            assert!(strings.iter().any(|s| !s.is_empty()), "{:?}", class);
+            for s in strings {
+                assert!(s.chars().all(is_in_class), "{:?} {:?}", class, s);
+            }
+        }
+    }
+
+    #[test]
+    fn test_mixed_char_classes() {
+        let config = FuzzConfig::new()
+            .with_string_length(10..=10)
+            .with_char_classes(vec![CharClass::PrintableAscii, CharClass::Astral]);
+
+        let strings = fuzz_strings(&config);
+        for s in &strings {
+            assert_eq!(10, s.chars().count(), "{:?}", s);
+        }
+
+        assert!(strings
+            .iter()
+            .any(|s| s.chars().any(|c| c.is_ascii()) && !s.is_ascii()));
+    }
+
+    #[test]
+    fn test_defined_properties() {
+        let defined = DefinedProperties::new(&[
+            "id".to_owned(),
+            "kind".to_owned(),
+            "status".to_owned(),
+            "\u{3c3}\u{3c4}\u{3bf}\u{3ac}".to_owned(),
+        ]);
+
+        // Strings that Go's strings.EqualFold considers equal.
+        assert!(defined.collides("ID"));
+        assert!(defined.collides("iD"));
+        assert!(defined.collides("\u{212a}ind"));
+        assert!(defined.collides("\u{17f}tatu\u{17f}"));
+        assert!(defined.collides("\u{3a3}\u{3a4}\u{39f}\u{386}"));
+        assert!(defined.collides("\u{3c2}\u{3c4}\u{3bf}\u{3ac}"));
+
+        // Strings that it doesn't.
+        assert!(!defined.collides("i"));
+        assert!(!defined.collides("id "));
+        assert!(!defined.collides("\u{130}d"));
+        assert!(!defined.collides("kinds"));
+    }
+
+    #[test]
+    fn test_additional_keys() {
+        let config = FuzzConfig::new().with_string_length(1..=1);
+        let defined = DefinedProperties::new(&["k".to_owned()]);
+
+        let mut rng = rand_pcg::Pcg32::seed_from_u64(8927);
+        let keys: Vec<_> = (0..1000)
+            .map(|_| fuzz_additional_key(&config, &mut rng, &defined))
+            .collect();
+
+        assert!(keys.contains(&None));
+        assert!(keys.iter().flatten().all(|k| k != "k" && k != "K"));
+    }
+
+    // Generates strings, both as values and as keys, and checks that they
+    // survive a round-trip through JSON.
+    fn fuzz_strings(config: &FuzzConfig) -> Vec<String> {
+        let schema = Schema::from_serde_schema(
+            serde_json::from_value(json!({ "values": { "type": "string" }})).unwrap(),
+        )
+        .unwrap();
+        let mut rng = rand_pcg::Pcg32::seed_from_u64(8927);
+
+        let mut strings = vec![];
+        for _ in 0..1000 {
+            let instance = crate::fuzz_with_config(&schema, config, &mut rng);
+            let errors = jtd::validate(&schema, &instance, Default::default()).unwrap();
+            assert!(errors.is_empty(), "{}", instance);
+
+            let json = serde_json::to_string(&instance).unwrap();
+            assert_eq!(
+                instance,
+                serde_json::from_str::<serde_json::Value>(&json).unwrap()
+            );
+
+            for (k, v) in instance.as_object().unwrap() {
+                strings.push(k.clone());
+                strings.push(v.as_str().unwrap().to_owned());
+            }
+        }
+
+        strings
+    }
+}
@@ -0,0 +1,74 @@
+// Fixtures shared by the tests of the different ways of generating values.
+
+use crate::FuzzConfig;
+use jtd::Schema;
+use rand::SeedableRng;
+use rand_pcg::Pcg32;
+use serde_json::{json, Value};
+use std::fmt::Debug;
+
+pub(crate) fn parse_schema(schema: Value) -> Schema {
+    Schema::from_serde_schema(serde_json::from_value(schema).unwrap()).unwrap()
+}
+
+// A schema with every form in it, recursive through both elements and a
+// discriminator, with nullable members and additional properties along the
+// way. Every instance of it can be encoded.
+pub(crate) fn recursive_schema() -> Schema {
+    parse_schema(json!({
+        "definitions": {
+            "node": {
+                "properties": {
+                    "value": { "type": "timestamp" },
+                    "children": { "elements": { "ref": "node" }},
+                },
+                "optionalProperties": {
+                    "tag": { "enum": ["a", "b"], "nullable": true },
+                    "weight": { "type": "float32" },
+                },
+                "additionalProperties": true,
+            },
+            "shape": {
+                "discriminator": "kind",
+                "mapping": {
+                    "leaf": {
+                        "properties": { "n": { "type": "int16" }},
+                        "additionalProperties": true,
+                    },
+                    "branch": {
+                        "properties": { "shapes": { "values": { "ref": "shape" }}},
+                    },
+                },
+            },
+        },
+        "properties": {
+            "node": { "ref": "node" },
+            "shape": { "ref": "shape", "nullable": true },
+            "any": {},
+            "count": { "type": "uint32" },
+            "floats": { "elements": { "type": "float64" }},
+        },
+    }))
+}
+
+// Checks that generate gives the same results as fuzz_with_config, after
+// passing them through expected, for n values in a row. Both are given RNGs in
+// the same state to begin with, and so must leave them in the same state too.
+pub(crate) fn assert_matches_fuzz<T, E, G>(
+    schema: &Schema,
+    config: &FuzzConfig,
+    n: usize,
+    expected: E,
+    mut generate: G,
+) where
+    T: PartialEq + Debug,
+    E: Fn(Value) -> T,
+    G: FnMut(&mut Pcg32) -> T,
+{
+    let mut rng = Pcg32::seed_from_u64(8927);
+    let mut other_rng = rng.clone();
+    for _ in 0..n {
+        let instance = crate::fuzz_with_config(schema, config, &mut rng);
+        assert_eq!(expected(instance), generate(&mut other_rng));
+    }
+}
@@ -0,0 +1,349 @@
+use crate::encode::Encoder;
+use crate::FuzzConfig;
+use chrono::{DateTime, Datelike, FixedOffset, NaiveDate, NaiveDateTime, TimeZone, Timelike, Utc};
+use rand::seq::SliceRandom;
+use rand::{Rng, RngCore};
+use std::convert::TryFrom;
+
+// The dates, all at 23:59:60 UTC, of some real leap seconds.
+const LEAP_SECOND_DATES: &[(i32, u32, u32)] = &[
+    (1972, 6, 30),
+    (1998, 12, 31),
+    (2008, 12, 31),
+    (2012, 6, 30),
+    (2015, 6, 30),
+    (2016, 12, 31),
+];
+
+// Leap years, including ones that are special cases of the leap year rules.
+const LEAP_YEARS: &[i32] = &[4, 1600, 1904, 1972, 2000, 2024, 2400, 9996];
+
+/// The earliest instant that can be written as an RFC3339 timestamp in UTC.
+pub(crate) fn min_timestamp() -> DateTime<Utc> {
+    Utc.ymd(1, 1, 1).and_hms(0, 0, 0)
+}
+
+/// The latest instant that can be written as an RFC3339 timestamp in UTC.
+pub(crate) fn max_timestamp() -> DateTime<Utc> {
+    Utc.ymd(9999, 12, 31).and_hms_nano(23, 59, 59, 999_999_999)
+}
+
+// Chooses an instant in the default range of timestamps, with a random offset
+// from UTC, for hints that format dates and times in other ways.
+pub(crate) fn fuzz_local_instant(rng: &mut dyn RngCore) -> DateTime<FixedOffset> {
+    // See fuzz_timestamp for why offsets are limited to fourteen hours.
+    let max_offset = 14 * 60;
+    let offset = FixedOffset::east(rng.gen_range(-max_offset..=max_offset) * 60);
+
+    let secs = rng.gen_range(i32::MIN as i64..=i32::MAX as i64);
+    let nanos = rng.gen_range(0..1_000_000_000);
+    offset.from_utc_datetime(&NaiveDateTime::from_timestamp(secs, nanos))
+}
+
+pub(crate) fn fuzz_timestamp<R: rand::Rng>(config: &FuzzConfig, rng: &mut R) -> String {
+    if config.timestamp_edges && rng.gen() {
+        return fuzz_timestamp_edge(config, rng);
+    }
+
+    // We'll generate timestamps with some random offset from UTC. Most of
+    // these random offsets will never have been used historically, but they
+    // can nonetheless be used in valid RFC3339 timestamps.
+    //
+    // However, UTC offsets present a practical complication:
+    //
+    // Java's java.time.ZoneOffset restricts offsets to no more than 18 hours
+    // from UTC:
+    //
+    // https://docs.oracle.com/javase/8/docs/api/java/time/ZoneOffset.html
+    //
+    // .NET's System.DateTimeOffset restricts offsets to no more than 14 hours
+    // from UTC:
+    //
+    // https://docs.microsoft.com/en-us/dotnet/api/system.datetimeoffset.tooffset?view=net-5.0
+    //
+    // To make jtd-fuzz work out of the box with these ecosystems, we will limit
+    // ourselves to the most selective of these time ranges.
+    //
+    // RFC3339 offsets only have minutes, not seconds, so we stick to whole
+    // minutes. The offset is still chosen in seconds, as fuzz() always has,
+    // and the seconds left over are added to the instant instead. That way,
+    // the default configuration writes the same timestamps from the same seed
+    // as it always has. For the same reason, the instant is chosen as an i32
+    // when it fits in one.
+    let max_offset = 14 * 60 * 60;
+    let (offset, extra_secs) = if config.utc_timestamps {
+        (None, 0)
+    } else {
+        let offset = rng.gen_range(-max_offset..=max_offset);
+        (Some(offset / 60 * 60), offset % 60)
+    };
+
+    // Choose a whole second, and then a fraction of that second. The fraction
+    // (and the seconds left over from the offset) may push the instant out of
+    // range at either end, so clamp it back in.
+    let (min, max) = (config.timestamp_range.start(), config.timestamp_range.end());
+    let secs = match (
+        i32::try_from(min.timestamp()),
+        i32::try_from(max.timestamp()),
+    ) {
+        (Ok(min), Ok(max)) => rng.gen_range(min..=max) as i64,
+        _ => rng.gen_range(min.timestamp()..=max.timestamp()),
+    };
+    let nanos = if config.timestamp_precision > 0 {
+        rng.gen_range(0..1_000_000_000)
+    } else {
+        0
+    };
+
+    let instant = Utc
+        .timestamp(secs + extra_secs as i64, nanos)
+        .max(*min)
+        .min(*max);
+    format(config, instant.naive_utc(), offset)
+}
+
+// The inverse of fuzz_timestamp, for encode(). Edge cases aren't supported.
+pub(crate) fn encode_timestamp(
+    config: &FuzzConfig,
+    enc: &mut Encoder,
+    timestamp: &str,
+) -> Result<(), &'static str> {
+    if config.timestamp_edges {
+        enc.bool(false);
+    }
+
+    let parsed = DateTime::parse_from_rfc3339(timestamp).map_err(|_| "invalid timestamp")?;
+    let instant = parsed.with_timezone(&Utc);
+    let utc = timestamp.ends_with('Z');
+
+    if instant.timestamp_subsec_nanos() >= 1_000_000_000 {
+        return Err("leap seconds are only generated as edge cases");
+    }
+
+    let max_offset = 14 * 60 * 60;
+    if config.utc_timestamps {
+        if !utc {
+            return Err("timestamp is not in UTC, but UTC timestamps are enabled");
+        }
+    } else {
+        let offset = if utc {
+            // format() only falls back to UTC when the offset would push the
+            // timestamp outside of years 0001 through 9999.
+            [-max_offset, max_offset]
+                .iter()
+                .copied()
+                .find(|&offset| {
+                    let local = FixedOffset::east(offset).from_utc_datetime(&instant.naive_utc());
+                    !(1..=9999).contains(&local.year())
+                })
+                .ok_or("timestamp is in UTC, but UTC timestamps are not enabled")?
+        } else {
+            parsed.offset().local_minus_utc()
+        };
+
+        if offset % 60 != 0 || offset.abs() > max_offset {
+            return Err("timestamp offset is more than fourteen hours from UTC");
+        }
+
+        enc.range_u32((offset + max_offset) as u32, (2 * max_offset) as u32);
+    }
+
+    let (min, max) = (config.timestamp_range.start(), config.timestamp_range.end());
+    if instant < *min || instant > *max {
+        return Err("timestamp is outside of the configured range");
+    }
+
+    let offset = (instant.timestamp() - min.timestamp()) as u64;
+    let span = (max.timestamp() - min.timestamp()) as u64;
+    if i32::try_from(min.timestamp()).is_ok() && i32::try_from(max.timestamp()).is_ok() {
+        if span == u32::MAX as u64 {
+            // rand uses the word as is for the full range of an i32.
+            enc.u32(instant.timestamp() as i32 as u32);
+        } else {
+            enc.range_u32(offset as u32, span as u32);
+        }
+    } else {
+        enc.range_u64(offset, span);
+    }
+
+    if config.timestamp_precision > 0 {
+        enc.range_u32(instant.timestamp_subsec_nanos(), 999_999_999);
+    }
+
+    Ok(())
+}
+
+fn fuzz_timestamp_edge<R: rand::Rng>(config: &FuzzConfig, rng: &mut R) -> String {
+    let nanos = rng.gen_range(0..1_000_000_000);
+    let time = |rng: &mut R| {
+        chrono::NaiveTime::from_hms_nano(
+            rng.gen_range(0..24),
+            rng.gen_range(0..60),
+            rng.gen_range(0..60),
+            nanos,
+        )
+    };
+
+    match rng.gen_range(0..5) {
+        // The first and last instants RFC3339 can represent.
+        0 => format(config, min_timestamp().naive_utc(), None),
+        1 => format(
+            config,
+            Utc.ymd(9999, 12, 31)
+                .and_hms_nano(23, 59, 59, 999_999_999)
+                .naive_utc(),
+            None,
+        ),
+
+        // A leap day.
+        2 => {
+            let year = *LEAP_YEARS.choose(rng).unwrap();
+            format(
+                config,
+                NaiveDate::from_ymd(year, 2, 29).and_time(time(rng)),
+                None,
+            )
+        }
+
+        // A leap second. Chrono represents these as having more than a billion
+        // nanoseconds.
+        3 => {
+            let (y, m, d) = *LEAP_SECOND_DATES.choose(rng).unwrap();
+            format(
+                config,
+                NaiveDate::from_ymd(y, m, d).and_hms_nano(23, 59, 59, 1_000_000_000 + nanos),
+                None,
+            )
+        }
+
+        // RFC3339 allows the "T" and "Z" in timestamps to be lowercase.
+        _ => {
+            let date = NaiveDate::from_ymd(rng.gen_range(1..=9999), 1, 1)
+                .with_ordinal(rng.gen_range(1..=365))
+                .unwrap();
+
+            format(config, date.and_time(time(rng)), None).to_lowercase()
+        }
+    }
+}
+
+// Formats an instant, given in UTC, as an RFC3339 timestamp with the given
+// offset (in seconds) from UTC, or with "Z" if offset is None.
+//
+// Fractional seconds are truncated, not rounded, to the configured precision,
+// so that rounding can never carry over into the next second.
+fn format(config: &FuzzConfig, instant: NaiveDateTime, offset: Option<i32>) -> String {
+    // Near the very start or end of what RFC3339 can represent, an offset may
+    // push the local time outside of years 0001 through 9999. In that case,
+    // fall back to UTC.
+    let offset = offset.and_then(|offset| {
+        let local = FixedOffset::east(offset).from_utc_datetime(&instant);
+        if (1..=9999).contains(&local.year()) {
+            Some(local)
+        } else {
+            None
+        }
+    });
+
+    let local = offset.map_or(instant, |local| local.naive_local());
+
+    let mut out = local.format("%Y-%m-%dT%H:%M:%S").to_string();
+
+    if config.timestamp_precision > 0 {
+        let nanos = format!("{:09}", local.nanosecond() % 1_000_000_000);
+        out.push('.');
+        out.push_str(&nanos[..config.timestamp_precision]);
+    }
+
+    match offset {
+        Some(local) => out.push_str(&local.format("%:z").to_string()),
+        None => out.push('Z'),
+    }
+
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use jtd::Schema;
+    use rand::SeedableRng;
+    use serde_json::json;
+
+    #[test]
+    fn test_timestamp_range() {
+        let (min, max) = (
+            Utc.ymd(2000, 1, 1).and_hms(0, 0, 0),
+            Utc.ymd(2000, 1, 1).and_hms(0, 0, 10),
+        );
+
+        let config = FuzzConfig::new().with_timestamp_range(min..=max);
+        for timestamp in fuzz_timestamps(&config) {
+            let parsed = DateTime::parse_from_rfc3339(&timestamp).unwrap();
+            assert!(min <= parsed && parsed <= max, "{}", timestamp);
+        }
+
+        let config = FuzzConfig::new().with_timestamp_range(min_timestamp()..=max_timestamp());
+        let years: Vec<_> = fuzz_timestamps(&config)
+            .iter()
+            .map(|timestamp| DateTime::parse_from_rfc3339(timestamp).unwrap().year())
+            .collect();
+
+        assert!(years.iter().any(|&year| year < 1000));
+        assert!(years.iter().any(|&year| year > 9000));
+    }
+
+    #[test]
+    fn test_timestamp_precision() {
+        for precision in 0..=9 {
+            let config = FuzzConfig::new()
+                .with_utc_timestamps(true)
+                .with_timestamp_precision(precision);
+
+            for timestamp in fuzz_timestamps(&config) {
+                assert!(timestamp.ends_with('Z'), "{}", timestamp);
+
+                // "YYYY-MM-DDTHH:MM:SS" is 19 characters, then there's a dot
+                // if there are any fractional seconds, and then the "Z".
+                let expected_len = 20 + precision + if precision > 0 { 1 } else { 0 };
+                assert_eq!(expected_len, timestamp.len(), "{}", timestamp);
+            }
+        }
+    }
+
+    #[test]
+    fn test_timestamp_edges() {
+        let config = FuzzConfig::new()
+            .with_timestamp_precision(3)
+            .with_timestamp_edges(true);
+
+        let timestamps = fuzz_timestamps(&config);
+        let any = |f: &dyn Fn(&str) -> bool| timestamps.iter().any(|t| f(t));
+
+        assert!(any(&|t| t.starts_with("0001-01-01T00:00:00.000Z")));
+        assert!(any(&|t| t.starts_with("9999-12-31T23:59:59.999Z")));
+        assert!(any(&|t| t.contains("-02-29T")));
+        assert!(any(&|t| t.contains("T23:59:60.")));
+        assert!(any(&|t| t.contains('t') && t.ends_with('z')));
+
+        // Edge cases are only generated half the time.
+        assert!(any(&|t| t.len() == "2000-01-01T00:00:00.000+00:00".len()));
+    }
+
+    fn fuzz_timestamps(config: &FuzzConfig) -> Vec<String> {
+        let schema = Schema::from_serde_schema(
+            serde_json::from_value(json!({ "type": "timestamp" })).unwrap(),
+        )
+        .unwrap();
+        let mut rng = rand_pcg::Pcg32::seed_from_u64(8927);
+
+        (0..1000)
+            .map(|_| {
+                let instance = crate::fuzz_with_config(&schema, config, &mut rng);
+                let errors = jtd::validate(&schema, &instance, Default::default()).unwrap();
+                assert!(errors.is_empty(), "{}", instance);
+                instance.as_str().unwrap().to_owned()
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,143 @@
+use crate::words::{fill_bytes_via_u32, next_u64_via_u32};
+use crate::{fuzz_with_config, FuzzConfig};
+use arbitrary::Unstructured;
+use jtd::Schema;
+use rand::RngCore;
+use serde_json::Value;
+
+/// Generates an instance of a schema from raw bytes, for use with
+/// coverage-guided fuzzers such as libFuzzer, `cargo fuzz`, or AFL.
+///
+/// This is only available with the `arbitrary` feature enabled. Instances are
+/// generated exactly as [`fuzz`](crate::fuzz) would, except that the bytes in
+/// `u` are used in place of random numbers. Whatever those bytes are, the
+/// instance is always valid against `schema`, so fuzzers are free to mutate
+/// them however they like.
+///
+/// Bytes are consumed four at a time, as little-endian 32-bit words. If `u`
+/// runs out of bytes, it is treated as if it were padded with zeroes, which
+/// tends to make the rest of the instance as small as possible: empty arrays,
+/// `null` where the schema is nullable, and so on.
+///
+/// ```
+/// use arbitrary::Unstructured;
+/// use serde_json::json;
+///
+/// let schema = jtd::Schema::from_serde_schema(serde_json::from_value(json!({
+///     "elements": { "type": "uint8" }
+/// })).unwrap()).unwrap();
+///
+/// // In a fuzz target, these bytes would come from the fuzzer.
+/// let mut u = Unstructured::new(&[0, 0, 0, 96, 1, 0, 0, 0, 2, 0, 0, 0]);
+///
+/// assert_eq!(json!([1, 2, 0]), jtd_fuzz::fuzz_unstructured(&schema, &mut u));
+/// ```
+pub fn fuzz_unstructured(schema: &Schema, u: &mut Unstructured) -> Value {
+    fuzz_unstructured_with_config(schema, &FuzzConfig::default(), u)
+}
+
+/// Generates an instance of a schema from raw bytes, with a custom
+/// configuration.
+///
+/// See [`fuzz_unstructured`] for details.
+pub fn fuzz_unstructured_with_config(
+    schema: &Schema,
+    config: &FuzzConfig,
+    u: &mut Unstructured,
+) -> Value {
+    let config = config.clone().with_zero_words_smallest();
+    fuzz_with_config(schema, &config, &mut UnstructuredRng { u })
+}
+
+// An RNG that takes its words from an Unstructured.
+struct UnstructuredRng<'a, 'b> {
+    u: &'a mut Unstructured<'b>,
+}
+
+impl<'a, 'b> RngCore for UnstructuredRng<'a, 'b> {
+    fn next_u32(&mut self) -> u32 {
+        // fill_buffer pads with zeroes once the data runs out, rather than
+        // failing.
+        let mut buf = [0; 4];
+        let _ = self.u.fill_buffer(&mut buf);
+        u32::from_le_bytes(buf)
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        next_u64_via_u32(self)
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        fill_bytes_via_u32(self, dest)
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), rand::Error> {
+        self.fill_bytes(dest);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::recursive_schema;
+    use rand::{Rng, SeedableRng};
+    use serde_json::json;
+
+    #[test]
+    fn test_fuzz_unstructured() {
+        let schema = recursive_schema();
+
+        let mut rng = rand_pcg::Pcg32::seed_from_u64(8927);
+        for _ in 0..1000 {
+            let len = rng.gen_range(0..1000);
+            let data: Vec<u8> = (0..len).map(|_| rng.gen()).collect();
+
+            let instance = fuzz_unstructured(&schema, &mut Unstructured::new(&data));
+            let errors = jtd::validate(&schema, &instance, Default::default()).unwrap();
+            assert!(errors.is_empty(), "{}", instance);
+
+            // The same bytes must always produce the same instance.
+            assert_eq!(
+                instance,
+                fuzz_unstructured(&schema, &mut Unstructured::new(&data))
+            );
+        }
+
+        // With no data at all, the smallest possible instance is generated.
+        assert_eq!(
+            json!({
+                "node": { "value": "1969-12-31T10:00:00-14:00", "children": [] },
+                "shape": null,
+                "any": null,
+                "count": 0,
+                "floats": [],
+            }),
+            fuzz_unstructured(&schema, &mut Unstructured::new(&[]))
+        );
+    }
+
+    #[test]
+    fn test_fuzz_unstructured_encoded() {
+        // Instances survive being encoded and then read back from bytes, even
+        // when the bytes run out partway through a word.
+        let schema = recursive_schema();
+
+        let mut rng = rand_pcg::Pcg32::seed_from_u64(8927);
+        let mut partial = 0;
+        for _ in 0..1000 {
+            let instance = crate::fuzz(&schema, &mut rng);
+            let bytes = crate::encode(&schema, &instance).unwrap();
+            if bytes.chunks(4).any(|word| word.len() < 4) {
+                partial += 1;
+            }
+
+            assert_eq!(
+                instance,
+                fuzz_unstructured(&schema, &mut Unstructured::new(&bytes))
+            );
+        }
+
+        assert!(partial > 0);
+    }
+}
@@ -0,0 +1,44 @@
+use rand::RngCore;
+
+// The RNGs that drive generation from recorded or externally supplied data
+// generate everything from 32-bit words, so that each word of the data
+// affects just one random choice, and can be shrunk or mutated independently.
+
+pub(crate) fn next_u64_via_u32<R: RngCore>(rng: &mut R) -> u64 {
+    let lo = rng.next_u32() as u64;
+    let hi = rng.next_u32() as u64;
+    hi << 32 | lo
+}
+
+pub(crate) fn fill_bytes_via_u32<R: RngCore>(rng: &mut R, dest: &mut [u8]) {
+    for chunk in dest.chunks_mut(4) {
+        chunk.copy_from_slice(&rng.next_u32().to_le_bytes()[..chunk.len()]);
+    }
+}
+
+// An RNG that plays back recorded words, and then zeroes once those run out.
+pub(crate) struct Replay {
+    pub(crate) words: Vec<u32>,
+    pub(crate) position: usize,
+}
+
+impl RngCore for Replay {
+    fn next_u32(&mut self) -> u32 {
+        let word = self.words.get(self.position).copied().unwrap_or(0);
+        self.position += 1;
+        word
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        next_u64_via_u32(self)
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        fill_bytes_via_u32(self, dest)
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), rand::Error> {
+        self.fill_bytes(dest);
+        Ok(())
+    }
+}